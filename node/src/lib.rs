@@ -1,7 +1,7 @@
 #[macro_use]
 extern crate napi_derive;
 
-use napi::{CallContext, JsObject, JsString, JsUndefined, Result};
+use napi::{CallContext, Error, JsObject, JsString, JsUndefined, Result};
 use std::ffi::OsString;
 
 #[module_exports]
@@ -22,10 +22,12 @@ fn run(ctx: CallContext) -> Result<JsUndefined> {
         }
 
         if let Err(e) = basalt::cli::run::<OsString>(Some(argv)) {
-            panic!("{}", e);
+            return Err(Error::from_reason(format!("{:#}", e)));
         }
     } else {
-        panic!("run(): argv must be an array of strings");
+        return Err(Error::from_reason(
+            "run(): argv must be an array of strings".to_string(),
+        ));
     }
 
     ctx.env.get_undefined()
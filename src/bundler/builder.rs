@@ -2,30 +2,32 @@
 
 use std::fs::File;
 use std::io::BufReader;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use anyhow::{bail, Context, Result};
+use anyhow::{Context, Result};
 
-use swc_common::{util::take::Take, FileName, SourceMap, DUMMY_SP};
+use swc_common::{util::take::Take, FileName, DUMMY_SP};
 use swc_ecma_ast::*;
-use swc_ecma_loader::{resolve::Resolve, resolvers::node::NodeModulesResolver};
 use swc_ecma_visit::{Fold, FoldWith};
 
 use serde::Serialize;
 
 use crate::{
-    module::base::module_base_directory,
-    policy::{Merge, Policy},
-    swc_utils::load_file,
+    policy::{builder::PolicyBuilder, Merge, Policy},
+    swc_utils::load_code,
 };
 
 use super::{
     loader::load_modules,
+    minify::minify_module,
+    runtime,
     serializer::{Serializer, Value},
+    validate::validate_policy,
 };
 
-const RUNTIME_PACKAGE: &str = "@lavamoat/lavapack";
+const INTEROP: &str = include_str!("interop.js");
+const INTEROP_FILE: &str = "interop.js";
 const MODULES: &str = "__modules__";
 const ENTRY_POINTS: &str = "__entryPoints__";
 const POLICY: &str = "__policy__";
@@ -35,30 +37,69 @@ const LOAD_BUNDLE: &str = "loadBundle";
 pub(crate) struct BundleBuilder {
     policy: Policy,
     program: Program,
-    source_map: Arc<SourceMap>,
-    resolver: Box<dyn Resolve>,
+    analysis: PolicyBuilder,
+    runtime: Option<PathBuf>,
+    strict_policy: bool,
+    minify: bool,
 }
 
 impl BundleBuilder {
-    /// Create a bundle builder.
-    pub fn new() -> Self {
-        let source_map: Arc<SourceMap> = Arc::new(Default::default());
+    /// Create a bundle builder that loads modules into the graph
+    /// of a policy builder.
+    pub fn new(analysis: PolicyBuilder) -> Self {
         let program = Program::Script(Script {
             span: DUMMY_SP,
             body: vec![],
             shebang: None,
         });
-        let resolver: Box<dyn Resolve> =
-            Box::new(NodeModulesResolver::default());
 
         Self {
             policy: Default::default(),
             program,
-            source_map,
-            resolver,
+            analysis,
+            runtime: None,
+            strict_policy: false,
+            minify: false,
         }
     }
 
+    /// Use a custom runtime file rather than the embedded runtime.
+    pub fn runtime(mut self, runtime: Option<&Path>) -> Self {
+        self.runtime = runtime.map(Path::to_path_buf);
+        self
+    }
+
+    /// Minify the module functions and the runtime.
+    pub fn minify(mut self, minify: bool) -> Self {
+        self.minify = minify;
+        self
+    }
+
+    /// Fail when the policy does not match the bundled modules
+    /// rather than logging warnings.
+    pub fn strict_policy(mut self, strict_policy: bool) -> Self {
+        self.strict_policy = strict_policy;
+        self
+    }
+
+    /// Generate the policy for the modules reachable from the entry points.
+    ///
+    /// The package for the first entry point is the root package in
+    /// the same way as when the modules are loaded into the bundle.
+    ///
+    /// Policy files loaded afterwards are merged as overrides.
+    pub fn generate_policy(mut self, entries: &[PathBuf]) -> Result<Self> {
+        if let Some(first) = entries.first() {
+            self.analysis = self.analysis.root_entry(first.clone());
+        }
+        for entry in entries {
+            self.analysis =
+                self.analysis.entry(entry.clone()).load()?.analyze()?;
+            self.policy.merge(self.analysis.policy());
+        }
+        Ok(self)
+    }
+
     /// Load policy files.
     pub fn load_policy_files(mut self, policy: &Vec<PathBuf>) -> Result<Self> {
         for file in policy {
@@ -76,7 +117,7 @@ impl BundleBuilder {
     }
 
     /// Fold into a single program.
-    pub fn fold(mut self, entry: PathBuf) -> Result<Self> {
+    pub fn fold(mut self, entries: Vec<PathBuf>) -> Result<Self> {
         // Load and inject the runtime
         let module = self.load_runtime_module()?;
         let mut runtime_module = RuntimeModule { module };
@@ -85,8 +126,8 @@ impl BundleBuilder {
         // [123, {'./util.js': 456 }, function(){ module.exports = 42 }, { package: '<root>' }]
 
         // Build modules data structure
-        let (expr, entry_point_ids) =
-            load_modules(entry, Arc::clone(&self.source_map), &self.resolver)?;
+        let (expr, entry_point_ids, packages) =
+            load_modules(&entries, self.analysis.graph(), self.minify)?;
         let mut modules_decl = ModulesDecl { expr };
         self.program = self.program.fold_children_with(&mut modules_decl);
 
@@ -95,12 +136,19 @@ impl BundleBuilder {
         let mut entries_decl = EntryPointsDecl { expr: entry_expr };
         self.program = self.program.fold_children_with(&mut entries_decl);
 
+        // Check the policy against the bundled packages
+        validate_policy(&mut self.policy, &packages, self.strict_policy)?;
+
         // Serialize and inject the computed policy
-        let policy_expr =
-            PolicyDecl::build_policy(std::mem::take(&mut self.policy))?;
+        let policy_expr = PolicyDecl::build_policy(&self.policy)?;
         let mut policy_decl = PolicyDecl { expr: policy_expr };
         self.program = self.program.fold_children_with(&mut policy_decl);
 
+        // Wrap ES module functors for interop with CommonJS modules
+        let module = self.load_interop_module()?;
+        let mut interop_module = RuntimeModule { module };
+        self.program = self.program.fold_children_with(&mut interop_module);
+
         // Initialize the bundle
         //
         // LavaPack.loadBundle(__modules__, __entryPoints__, __policy__)
@@ -115,39 +163,43 @@ impl BundleBuilder {
     }
 
     /// Finalize the bundled program.
-    pub fn finalize(self) -> (Program, Arc<SourceMap>) {
-        (self.program, self.source_map)
+    pub fn finalize(self) -> (Program, PolicyBuilder, Policy) {
+        (self.program, self.analysis, self.policy)
     }
 
     /// Load the runtime module.
     fn load_runtime_module(&self) -> Result<Module> {
-        let base_dir = FileName::Real(std::env::current_dir()?);
-        let runtime_lib = self
-            .resolver
-            .resolve(&base_dir, RUNTIME_PACKAGE)
-            .context(format!(
-                "could not find {}, ensure it has been installed",
-                RUNTIME_PACKAGE
-            ))?;
+        let module = runtime::load(
+            self.runtime.as_deref(),
+            Arc::clone(self.analysis.graph().source_map()),
+        )?;
+        Ok(self.maybe_minify(module))
+    }
 
-        let lib_index = match runtime_lib {
-            FileName::Real(path) => path,
-            _ => bail!("runtime library must be a real path"),
-        };
-        let package_dir = module_base_directory(&lib_index).unwrap();
-        let runtime_file = package_dir.join("src").join("runtime.js");
+    /// Load the module that wraps ES module functors so that
+    /// the runtime can call them as CommonJS modules.
+    fn load_interop_module(&self) -> Result<Module> {
+        let (_, _, module) = load_code(
+            INTEROP,
+            Some(FileName::Custom(INTEROP_FILE.into())),
+            Some(Arc::clone(self.analysis.graph().source_map())),
+        )?;
+        Ok(self.maybe_minify(module))
+    }
 
-        if !runtime_file.is_file() {
-            bail!("runtime {} is not a file", runtime_file.display());
+    /// Minify a runtime module when minifying the bundle.
+    fn maybe_minify(&self, module: Module) -> Module {
+        if self.minify {
+            minify_module(
+                module,
+                Arc::clone(self.analysis.graph().source_map()),
+            )
+        } else {
+            module
         }
-
-        let (_, _, module) =
-            load_file(&runtime_file, Some(Arc::clone(&self.source_map)))?;
-
-        Ok(module)
     }
 
-    fn build_entry_points(&self, ids: Vec<u32>) -> Result<Expr> {
+    fn build_entry_points(&self, ids: Vec<String>) -> Result<Expr> {
         let mut serializer = Serializer {};
         let value = ids.serialize(&mut serializer)?;
         if let Value::Array(arr) = value {
@@ -157,7 +209,8 @@ impl BundleBuilder {
     }
 }
 
-/// Inject the LavaPack runtime.
+/// Inject the statements of a runtime module such
+/// as the LavaPack runtime.
 struct RuntimeModule {
     module: Module,
 }
@@ -241,7 +294,7 @@ struct PolicyDecl {
 }
 
 impl PolicyDecl {
-    fn build_policy(policy: Policy) -> Result<Expr> {
+    fn build_policy(policy: &Policy) -> Result<Expr> {
         let mut serializer = Serializer {};
         let value = policy.serialize(&mut serializer)?;
         if let Value::Object(obj) = value {
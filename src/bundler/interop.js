@@ -0,0 +1,133 @@
+// Interop between ES modules and CommonJS modules in a bundle.
+//
+// The runtime calls every module as a CommonJS module so the functors
+// for ES modules (type `esm`) and mixed modules (type `mixed`) are
+// wrapped in a function that takes the `require`, `module` and `exports`
+// arguments.
+//
+// ES module exports are defined as enumerable getters on `exports`
+// together with a non-enumerable `__esModule` flag so that CommonJS
+// modules can `require()` an ES module and read the `default` export.
+//
+// Mixed modules may assign `module.exports` so their ES module exports
+// are defined on the current value of `module.exports` and the
+// `__esModule` flag is only set once a mixed module exports a name; a
+// mixed module that only uses CommonJS exports is imported in the same
+// way as a CommonJS module.
+//
+// When an ES module imports a CommonJS module the namespace is derived
+// from `module.exports`; exports flagged with `__esModule` are used as
+// the namespace otherwise the `default` export is `module.exports` and
+// the named exports are the own enumerable properties of `module.exports`.
+//
+// Imported bindings are assigned when the imports are executed so they
+// are not updated by later assignments in the exporting module.
+(function (modules) {
+  const ESM = 'esm';
+  const MIXED = 'mixed';
+  const ALL = '*';
+  const DEFAULT = 'default';
+
+  function isObject(value) {
+    return (
+      value !== null &&
+      (typeof value === 'object' || typeof value === 'function')
+    );
+  }
+
+  function namespace(exports) {
+    if (exports && exports.__esModule) {
+      return exports;
+    }
+    const ns = Object.create(null);
+    if (isObject(exports)) {
+      Object.keys(exports).forEach(function (key) {
+        if (key !== DEFAULT) {
+          ns[key] = exports[key];
+        }
+      });
+    }
+    ns[DEFAULT] = exports;
+    return ns;
+  }
+
+  function define(exports, name, get) {
+    Object.defineProperty(exports, name, {
+      enumerable: true,
+      configurable: true,
+      get: get,
+    });
+  }
+
+  function wrap(functor, mixed) {
+    return function (require, module, exports) {
+      const values = Object.create(null);
+
+      // Object that receives the ES module exports
+      function exportTarget() {
+        const current = mixed ? module.exports : exports;
+        if (isObject(current) && !current.__esModule) {
+          Object.defineProperty(current, '__esModule', { value: true });
+        }
+        return current;
+      }
+
+      if (!mixed) {
+        exportTarget();
+      }
+
+      function imports(updates, exportAlls) {
+        updates.forEach(function (names, specifier) {
+          const ns = namespace(require(specifier));
+          names.forEach(function (setters, name) {
+            const value = name === ALL ? ns : ns[name];
+            setters.forEach(function (setter) {
+              setter(value);
+            });
+          });
+        });
+        exportAlls.forEach(function (specifier) {
+          const ns = namespace(require(specifier));
+          Object.keys(ns).forEach(function (name) {
+            if (name !== DEFAULT && !(name in values)) {
+              define(exportTarget(), name, function () {
+                return ns[name];
+              });
+            }
+          });
+        });
+      }
+
+      const exported = new Proxy(
+        {},
+        {
+          get: function (target, name) {
+            return function (value) {
+              if (!(name in values)) {
+                define(exportTarget(), name, function () {
+                  return values[name];
+                });
+              }
+              values[name] = value;
+            };
+          },
+        },
+      );
+
+      const args = { imports: imports, liveVar: exported, onceVar: exported };
+      if (mixed) {
+        args.require = require;
+        args.module = module;
+        args.exports = exports;
+      }
+      return functor(args);
+    };
+  }
+
+  modules.forEach(function (entry) {
+    const options = entry[3];
+    if (options.type === ESM || options.type === MIXED) {
+      entry[2] = wrap(entry[2], options.type === MIXED);
+    }
+  });
+})(__modules__);
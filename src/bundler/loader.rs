@@ -1,98 +1,248 @@
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use anyhow::{bail, Result};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-use swc_common::{FileName, SourceMap, DUMMY_SP};
+use swc_common::{BytePos, FileName, SourceMap, Span, DUMMY_SP};
 use swc_ecma_ast::*;
-use swc_ecma_loader::resolve::Resolve;
-use swc_ecma_visit::{Node, Visit, VisitWith};
+use swc_ecma_visit::{VisitMut, VisitMutWith};
 
 use crate::{
-    helpers::{
-        is_module_exports, normalize_specifier, EXPORTS, MODULE, REQUIRE,
-    },
+    cache::Cache,
+    helpers::{normalize_specifier, relative_path, EXPORTS, MODULE, REQUIRE},
     module::{
         dependencies::is_dependent_module,
-        node::{
-            cached_modules, parse_module, VisitedDependency, VisitedModule,
-        },
+        graph::ModuleGraph,
+        kind::{declared_kind, module_kind, ModuleKind},
+        node::{ModuleNode, VisitedDependency, VisitedModule},
+    },
+    static_module_record::transform::{
+        transform_mixed_module_function, transform_module_function,
     },
-    static_module_record::transform::transform_module_function,
+    swc_utils::{has_jsx, transform_jsx},
 };
 
-use super::serializer::Serializer;
+use super::{minify::minify_function, serializer::Serializer};
 
 const ROOT_PACKAGE: &str = "<root>";
 
+/// Cache entry kind for module functions.
+const FUNCTIONS: &str = "functions";
+
+/// Packages in a bundle and the packages that each of them imports.
+///
+/// The root package is not included.
+pub(super) type BundlePackages = BTreeMap<String, BTreeSet<String>>;
+
+/// Module function stored in the analysis cache.
+///
+/// Spans are relative to the start of the source file as the
+/// position of the file in the source map changes between builds.
+#[derive(Serialize, Deserialize)]
+struct CachedFunction {
+    function: Box<Expr>,
+    kind: ModuleKind,
+}
+
+/// Move spans from the position of a source file in one source map
+/// to another position.
+struct MoveSpans {
+    from: BytePos,
+    to: BytePos,
+}
+
+impl VisitMut for MoveSpans {
+    fn visit_mut_span(&mut self, span: &mut Span) {
+        if !span.is_dummy() {
+            span.lo = span.lo - self.from + self.to;
+            span.hi = span.hi - self.from + self.to;
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct ModuleOptions {
     pub package: String,
     pub r#type: ModuleKind,
 }
 
-#[derive(Debug, Serialize)]
-pub enum ModuleKind {
-    #[serde(skip)]
-    Mixed,
-    #[serde(rename = "esm")]
-    Esm,
-    #[serde(rename = "js")]
-    Cjs,
-}
+pub(super) fn load_modules(
+    entries: &[PathBuf],
+    graph: &ModuleGraph,
+    minify: bool,
+) -> Result<(Expr, Vec<String>, BundlePackages)> {
+    let file = match entries.first() {
+        Some(file) => file,
+        None => bail!("Bundle requires at least one entry point"),
+    };
+
+    // Modules in the package for the first entry point belong
+    // to the root package
+    let root = graph.package(file).map(|package| package.base);
+
+    // Module ids in the root package are relative to the base directory
+    let base = match &root {
+        Some(base) => base.clone(),
+        None => file.parent().unwrap().to_path_buf(),
+    };
 
-pub(super) fn load_modules<P: AsRef<Path>>(
-    file: P,
-    source_map: Arc<SourceMap>,
-    resolver: &Box<dyn Resolve>,
-) -> Result<(Expr, Vec<u32>)> {
     let mut list = Vec::new();
-    let module =
-        parse_module(file.as_ref(), resolver, Arc::clone(&source_map))?;
-
-    let root_entry_id = {
-        let cached = cached_modules();
-        let entry = cached.get(file.as_ref()).unwrap();
-        let value = entry.value();
-        match &**value {
-            VisitedModule::Module(_, node) | VisitedModule::Json(_, node) => {
-                node.id
+
+    // Modules shared between entry points are only added once
+    let mut seen: HashSet<PathBuf> = HashSet::new();
+
+    for file in entries {
+        let module = graph.load(file)?;
+
+        if let VisitedModule::Builtin(_) = &*module {
+            unreachable!("Main entry point cannot be a builtin");
+        }
+
+        if seen.insert(file.clone()) {
+            // Entry points outside the root package belong to their package
+            let spec = match graph.package(file) {
+                Some(package) if Some(&package.base) != root.as_ref() => {
+                    package.name
+                }
+                _ => ROOT_PACKAGE.to_string(),
+            };
+            list.push((spec, Arc::clone(&module)));
+        }
+
+        // Visit the module graph and collect the module nodes
+        let mut visitor = |dep: VisitedDependency| {
+            if let FileName::Real(path) = &dep.file_name {
+                if !seen.contains(path) {
+                    if let Some(module) = graph.get(path) {
+                        seen.insert(path.clone());
+                        let spec =
+                            package_spec(graph, path, &dep.spec, root.as_ref());
+                        list.push((spec, module));
+                    }
+                }
             }
-            _ => unreachable!("Main entry point cannot be a builtin"),
+            Ok(())
+        };
+
+        if let VisitedModule::Module(_, node) = &*module {
+            node.walk(graph, &mut visitor)?;
         }
-    };
+    }
 
-    // Add the root entry point module
-    list.push((ROOT_PACKAGE.to_string(), Arc::clone(&module)));
-
-    // Visit the module graph and collect the module nodes
-    let mut visitor = |dep: VisitedDependency| {
-        if let FileName::Real(path) = &dep.file_name {
-            let cached = cached_modules();
-            if let Some(item) = cached.get(path) {
-                let module = item.value();
-                let spec = if is_dependent_module(&dep.spec) {
-                    normalize_specifier(dep.spec)
-                } else {
-                    ROOT_PACKAGE.to_string()
+    let ids = module_ids(graph, &list, root.as_ref(), &base);
+    let entry_ids = entries.iter().map(|file| ids[file].clone()).collect();
+    let packages = bundle_packages(graph, &list, root.as_ref());
+    Ok((
+        transform_modules(graph, list, &ids, minify)?,
+        entry_ids,
+        packages,
+    ))
+}
+
+/// Get the identifiers for the modules in a bundle.
+///
+/// Modules in the root package are identified by the path relative
+/// to the root package and modules in other packages by the package
+/// name and version followed by the path relative to the package so
+/// identifiers do not depend upon where the packages are installed.
+///
+/// When the same version of a package is installed more than once
+/// the identifiers would be the same so the module with the greater
+/// path is identified by the path relative to the root package.
+fn module_ids(
+    graph: &ModuleGraph,
+    modules: &[(String, Arc<VisitedModule>)],
+    root: Option<&PathBuf>,
+    base: &Path,
+) -> HashMap<PathBuf, String> {
+    let mut paths: Vec<&PathBuf> = modules
+        .iter()
+        .filter_map(|(_, item)| match &**item {
+            VisitedModule::Module(FileName::Real(path), _)
+            | VisitedModule::Json(FileName::Real(path), _) => Some(path),
+            _ => None,
+        })
+        .collect();
+    paths.sort();
+
+    let mut ids = HashMap::new();
+    let mut used = HashSet::new();
+    for path in paths {
+        let id = match graph.package(path) {
+            Some(package) if Some(&package.base) != root => {
+                let name = match &package.version {
+                    Some(version) => format!("{}@{}", package.name, version),
+                    None => package.name,
                 };
-                list.push((spec, Arc::clone(module)));
+                format!("{}/{}", name, relative_path(&package.base, path))
             }
-        }
-        Ok(())
-    };
+            _ => relative_path(base, path),
+        };
+        let id = if used.contains(&id) {
+            relative_path(base, path)
+        } else {
+            id
+        };
+        used.insert(id.clone());
+        ids.insert(path.clone(), id);
+    }
+    ids
+}
 
-    if let VisitedModule::Module(_, node) = &*module {
-        node.visit(source_map, &mut visitor)?;
+/// Get the package name for a module.
+fn package_spec(
+    graph: &ModuleGraph,
+    path: &Path,
+    specifier: &str,
+    root: Option<&PathBuf>,
+) -> String {
+    match graph.package(path) {
+        Some(package) if Some(&package.base) != root => package.name,
+        None if is_dependent_module(specifier) => {
+            normalize_specifier(specifier)
+        }
+        _ => ROOT_PACKAGE.to_string(),
     }
+}
 
-    Ok((transform_modules(list)?, vec![root_entry_id]))
+/// Collect the packages in a bundle and the packages they import.
+fn bundle_packages(
+    graph: &ModuleGraph,
+    modules: &[(String, Arc<VisitedModule>)],
+    root: Option<&PathBuf>,
+) -> BundlePackages {
+    let mut packages: BundlePackages = BTreeMap::new();
+    for (spec, item) in modules {
+        let module = match &**item {
+            VisitedModule::Module(_, module)
+            | VisitedModule::Json(_, module) => module,
+            _ => continue,
+        };
+        let imports: BTreeSet<String> = module
+            .resolved
+            .iter()
+            .filter_map(|(specifier, file_name)| match file_name {
+                FileName::Real(path) => {
+                    Some(package_spec(graph, path, specifier, root))
+                }
+                _ => None,
+            })
+            .filter(|dep| dep != spec && dep != ROOT_PACKAGE)
+            .collect();
+        if spec != ROOT_PACKAGE {
+            packages.entry(spec.clone()).or_default().extend(imports);
+        }
+    }
+    packages
 }
 
 fn transform_modules(
+    graph: &ModuleGraph,
     modules: Vec<(String, Arc<VisitedModule>)>,
+    ids: &HashMap<PathBuf, String>,
+    minify: bool,
 ) -> Result<Expr> {
     let mut serializer = Serializer {};
 
@@ -104,34 +254,17 @@ fn transform_modules(
     //let mut out = Vec::new();
     for (spec, item) in modules {
         match &*item {
-            VisitedModule::Module(_, module)
-            | VisitedModule::Json(_, module) => {
-                let dependencies: HashMap<String, u32> = module
+            VisitedModule::Module(file_name, module)
+            | VisitedModule::Json(file_name, module) => {
+                let dependencies: BTreeMap<String, String> = module
                     .resolved
                     .iter()
-                    .map(|(spec, file_name)| {
-                        let id: Option<u32> =
-                            if let FileName::Real(path) = &file_name {
-                                let cached = cached_modules();
-                                if let Some(item) = cached.get(path) {
-                                    let module = item.value();
-                                    match &**module {
-                                        VisitedModule::Module(_, module)
-                                        | VisitedModule::Json(_, module) => {
-                                            Some(module.id)
-                                        }
-                                        _ => None,
-                                    }
-                                } else {
-                                    None
-                                }
-                            } else {
-                                None
-                            };
-                        return (spec.to_string(), id);
+                    .filter_map(|(spec, file_name)| match file_name {
+                        FileName::Real(path) => ids
+                            .get(path)
+                            .map(|id| (spec.to_string(), id.clone())),
+                        _ => None,
                     })
-                    .filter(|(_, id)| id.is_some())
-                    .map(|(spec, id)| (spec, id.unwrap()))
                     .collect();
 
                 let mut item = ArrayLit {
@@ -140,7 +273,11 @@ fn transform_modules(
                 };
 
                 // Module id
-                let id = module.id.serialize(&mut serializer)?;
+                let path = match file_name {
+                    FileName::Real(path) => path,
+                    _ => bail!("module {} is not a file", file_name),
+                };
+                let id = ids[path].serialize(&mut serializer)?;
                 item.elems.push(Some(ExprOrSpread {
                     spread: None,
                     expr: id.into_boxed_expr(),
@@ -154,7 +291,13 @@ fn transform_modules(
                 }));
 
                 // Transform to init function
-                let (init_fn, kind) = into_module_function(&*module.module)?;
+                let (mut init_fn, kind) = module_function(graph, path, module)?;
+                if minify {
+                    init_fn = minify_function(
+                        init_fn,
+                        Arc::clone(graph.source_map()),
+                    );
+                }
                 item.elems.push(Some(ExprOrSpread {
                     spread: None,
                     expr: init_fn,
@@ -184,20 +327,93 @@ fn transform_modules(
     Ok(Expr::Array(arr))
 }
 
-fn into_module_function(module: &Module) -> Result<(Box<Expr>, ModuleKind)> {
-    let mut detector = Es6Detector {
-        esm: false,
-        cjs: false,
+/// Get the function for a module.
+///
+/// When an analysis cache is assigned functions are cached by the
+/// module contents and declared kind so that modules which have not
+/// changed are not parsed or transformed again; on a cache miss
+/// modules loaded from the analysis cache are parsed again.
+fn module_function(
+    graph: &ModuleGraph,
+    path: &Path,
+    module: &ModuleNode,
+) -> Result<(Box<Expr>, ModuleKind)> {
+    let file_name = FileName::Real(path.to_path_buf());
+    let cache = graph.analysis_cache();
+    let key = match (cache, &module.hash) {
+        (Some(_), Some(hash)) => {
+            let declared = format!("{:?}", declared_kind(path));
+            Some(Cache::key(&[
+                FUNCTIONS.as_bytes(),
+                hash.as_bytes(),
+                declared.as_bytes(),
+            ]))
+        }
+        _ => None,
     };
-    module.visit_children_with(&mut detector);
 
-    let kind = detector.kind();
+    let cached = cache
+        .zip(key.as_ref())
+        .and_then(|(cache, key)| cache.get::<CachedFunction>(FUNCTIONS, key));
+    if let Some(mut cached) = cached {
+        let source_map = graph.source_map();
+        let source_file = match source_map.get_source_file(&file_name) {
+            Some(source_file) => source_file,
+            None => source_map.load_file(path)?,
+        };
+        cached.function.visit_mut_with(&mut MoveSpans {
+            from: BytePos(0),
+            to: source_file.start_pos,
+        });
+        return Ok((cached.function, cached.kind));
+    }
+
+    let reparsed = graph.reparse(&file_name, module)?;
+    let ast = match &reparsed.as_ref().unwrap_or(module).module {
+        Some(ast) => Arc::clone(ast),
+        None => bail!("module {} was not parsed", file_name),
+    };
+    let (function, kind) =
+        into_module_function(&file_name, &*ast, graph.source_map())?;
+
+    if let (Some(cache), Some(key)) = (cache, &key) {
+        if let Some(source_file) =
+            graph.source_map().get_source_file(&file_name)
+        {
+            let mut function = function.clone();
+            function.visit_mut_with(&mut MoveSpans {
+                from: source_file.start_pos,
+                to: BytePos(0),
+            });
+            cache.put(FUNCTIONS, key, &CachedFunction { function, kind })?;
+        }
+    }
+    Ok((function, kind))
+}
+
+fn into_module_function(
+    file_name: &FileName,
+    module: &Module,
+    source_map: &Arc<SourceMap>,
+) -> Result<(Box<Expr>, ModuleKind)> {
+    let file = match file_name {
+        FileName::Real(path) => Some(path.as_path()),
+        _ => None,
+    };
+
+    // JSX is compiled before the module is transformed
+    let compiled;
+    let module = if has_jsx(module) {
+        compiled = transform_jsx(module.clone(), Arc::clone(source_map));
+        &compiled
+    } else {
+        module
+    };
+    let kind = module_kind(file, module)?;
     match &kind {
         ModuleKind::Esm => Ok((transform_esm(module)?, kind)),
         ModuleKind::Cjs => Ok((transform_cjs(module)?, kind)),
-        ModuleKind::Mixed => {
-            bail!("ESM and CJS modules may not be combined")
-        }
+        ModuleKind::Mixed => Ok((transform_mixed(module)?, kind)),
     }
 }
 
@@ -209,6 +425,18 @@ fn transform_esm(module: &Module) -> Result<Box<Expr>> {
     })))
 }
 
+/// Mixed modules are called by the runtime with a single object
+/// containing the static module record functor arguments (`imports`,
+/// `liveVar` and `onceVar`) and the `require`, `module` and `exports`
+/// bindings.
+fn transform_mixed(module: &Module) -> Result<Box<Expr>> {
+    let (_meta, function) = transform_mixed_module_function(module)?;
+    Ok(Box::new(Expr::Fn(FnExpr {
+        ident: None,
+        function,
+    })))
+}
+
 fn transform_cjs(module: &Module) -> Result<Box<Expr>> {
     let expr = Expr::Fn(FnExpr {
         ident: None,
@@ -276,47 +504,3 @@ fn transform_cjs(module: &Module) -> Result<Box<Expr>> {
     });
     Ok(Box::new(expr))
 }
-
-struct Es6Detector {
-    esm: bool,
-    cjs: bool,
-}
-
-impl Es6Detector {
-    fn kind(&self) -> ModuleKind {
-        if self.esm && self.cjs {
-            ModuleKind::Mixed
-        } else if self.esm {
-            ModuleKind::Esm
-        } else {
-            ModuleKind::Cjs
-        }
-    }
-}
-
-impl Visit for Es6Detector {
-    fn visit_module_item(&mut self, n: &ModuleItem, _: &dyn Node) {
-        match n {
-            ModuleItem::ModuleDecl(n) => match n {
-                ModuleDecl::Import(_)
-                | ModuleDecl::ExportDecl(_)
-                | ModuleDecl::ExportNamed(_)
-                | ModuleDecl::ExportDefaultDecl(_)
-                | ModuleDecl::ExportDefaultExpr(_)
-                | ModuleDecl::ExportAll(_) => {
-                    self.esm = true;
-                }
-                _ => {}
-            },
-            ModuleItem::Stmt(n) => {
-                if let Stmt::Expr(n) = n {
-                    if let Expr::Assign(n) = &*n.expr {
-                        if is_module_exports(&n.left) {
-                            self.cjs = true;
-                        }
-                    }
-                }
-            }
-        }
-    }
-}
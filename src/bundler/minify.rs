@@ -0,0 +1,106 @@
+//! Minify the module functions and runtime for a bundle.
+//!
+//! Identifiers are mangled and the bundle is printed without
+//! whitespace, the original spans are kept so the source map
+//! for the bundle still maps to the original modules.
+use std::sync::Arc;
+
+use swc::ecmascript::minifier::{
+    optimize,
+    option::{ExtraOptions, MangleOptions, MinifyOptions},
+};
+use swc_atoms::JsWord;
+use swc_common::{Globals, Mark, SourceMap, DUMMY_SP, GLOBALS};
+use swc_ecma_ast::*;
+use swc_ecma_transforms_base::{fixer::fixer, resolver::resolver_with_mark};
+use swc_ecma_visit::{FoldWith, Node, Visit, VisitWith};
+
+use crate::static_module_record::transform::{
+    HIDDEN_CONST_VAR_PREFIX, HIDDEN_PREFIX,
+};
+
+/// Minify a module function.
+///
+/// The hidden identifiers used by static module record functors
+/// are reserved so they are not renamed.
+pub(super) fn minify_function(
+    expr: Box<Expr>,
+    source_map: Arc<SourceMap>,
+) -> Box<Expr> {
+    let mut hidden = HiddenIdents::default();
+    expr.visit_children_with(&mut hidden);
+
+    let module = Module {
+        span: DUMMY_SP,
+        body: vec![ModuleItem::Stmt(Stmt::Expr(ExprStmt {
+            span: DUMMY_SP,
+            expr,
+        }))],
+        shebang: None,
+    };
+    let mut module = minify(module, source_map, hidden.names);
+    match module.body.pop() {
+        Some(ModuleItem::Stmt(Stmt::Expr(ExprStmt { expr, .. }))) => {
+            match *expr {
+                Expr::Paren(ParenExpr { expr, .. }) => expr,
+                _ => expr,
+            }
+        }
+        _ => unreachable!("minified module function must be an expression"),
+    }
+}
+
+/// Minify a module such as the runtime.
+///
+/// Top-level declarations are not renamed.
+pub(super) fn minify_module(
+    module: Module,
+    source_map: Arc<SourceMap>,
+) -> Module {
+    minify(module, source_map, Vec::new())
+}
+
+fn minify(
+    module: Module,
+    source_map: Arc<SourceMap>,
+    reserved: Vec<JsWord>,
+) -> Module {
+    GLOBALS.set(&Globals::new(), || {
+        let top_level_mark = Mark::fresh(Mark::root());
+        let module = module.fold_with(&mut resolver_with_mark(top_level_mark));
+        let options = MinifyOptions {
+            mangle: Some(MangleOptions {
+                reserved,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let module = optimize(
+            module,
+            source_map,
+            None,
+            None,
+            &options,
+            &ExtraOptions { top_level_mark },
+        );
+        module.fold_with(&mut fixer(None))
+    })
+}
+
+/// Collect the hidden identifiers in a module function.
+#[derive(Default)]
+struct HiddenIdents {
+    names: Vec<JsWord>,
+}
+
+impl Visit for HiddenIdents {
+    fn visit_ident(&mut self, n: &Ident, _: &dyn Node) {
+        let name = n.sym.as_ref();
+        if (name.starts_with(HIDDEN_PREFIX)
+            || name.starts_with(HIDDEN_CONST_VAR_PREFIX))
+            && !self.names.contains(&n.sym)
+        {
+            self.names.push(n.sym.clone());
+        }
+    }
+}
@@ -2,30 +2,110 @@
 
 use anyhow::{Context, Result};
 use std::path::PathBuf;
-use std::sync::Arc;
-use swc_common::SourceMap;
 use swc_ecma_ast::Program;
 
+use crate::{
+    cache::Cache,
+    module::graph::ModuleGraph,
+    policy::{builder::PolicyBuilder, Policy},
+};
+
 mod builder;
 mod loader;
+mod minify;
+mod runtime;
 mod serializer;
+mod validate;
+
+pub use runtime::RUNTIME_VERSION;
 
 /// Options for bundling.
 #[derive(Debug)]
 pub struct BundleOptions {
-    pub(crate) module: PathBuf,
+    pub(crate) modules: Vec<PathBuf>,
     pub(crate) policy: Vec<PathBuf>,
+    pub(crate) keep_going: bool,
+    pub(crate) cache: Option<Cache>,
+    pub(crate) conditions: Vec<String>,
+    pub(crate) pnp: Option<PathBuf>,
+    pub(crate) runtime: Option<PathBuf>,
+    pub(crate) strict_policy: bool,
+    pub(crate) minify: bool,
 }
 
 /// Generate a bundle from the given options.
-pub fn bundle(options: BundleOptions) -> Result<(Program, Arc<SourceMap>)> {
-    let builder = builder::BundleBuilder::new();
-    let module = options
-        .module
-        .canonicalize()
-        .context("Failed to determine canonical path for module entry point")?;
+///
+/// The policy builder is returned so callers can access the module
+/// graph for the source map and any errors collected whilst loading
+/// modules together with the policy embedded in the bundle.
+pub fn bundle(
+    options: BundleOptions,
+) -> Result<(Program, PolicyBuilder, Policy)> {
+    let analysis = policy_builder(&options)?;
+    bundle_with(&options, analysis)
+}
+
+/// Create a policy builder with a module graph for the given options.
+pub(crate) fn policy_builder(options: &BundleOptions) -> Result<PolicyBuilder> {
+    let entry = options
+        .modules
+        .first()
+        .context("at least one entry point is required to bundle")?;
+    Ok(PolicyBuilder::with_graph(
+        entry.clone(),
+        module_graph(options)?,
+    ))
+}
+
+/// Create a module graph for the given options.
+pub(crate) fn module_graph(options: &BundleOptions) -> Result<ModuleGraph> {
+    let graph = match &options.pnp {
+        Some(manifest) => ModuleGraph::with_pnp(
+            manifest,
+            options.conditions.clone(),
+            options.cache.as_ref(),
+        )?,
+        None => ModuleGraph::with_conditions(options.conditions.clone()),
+    };
+    Ok(graph
+        .keep_going(options.keep_going)
+        .cache(options.cache.clone()))
+}
+
+/// Comment placed at the start of a bundle that
+/// includes the version of the runtime.
+pub fn header(options: &BundleOptions) -> String {
+    runtime::header(options.runtime.as_deref())
+}
+
+/// Generate a bundle using an existing policy builder.
+///
+/// Modules already loaded into the graph are not parsed or analyzed
+/// again which allows a bundle to be regenerated after invalidating
+/// the modules that have changed.
+///
+/// The policy is generated from the modules in the graph and
+/// the policy files are merged as overrides.
+pub fn bundle_with(
+    options: &BundleOptions,
+    analysis: PolicyBuilder,
+) -> Result<(Program, PolicyBuilder, Policy)> {
+    let builder = builder::BundleBuilder::new(analysis)
+        .runtime(options.runtime.as_deref())
+        .strict_policy(options.strict_policy)
+        .minify(options.minify);
+    let modules = options
+        .modules
+        .iter()
+        .map(|module| {
+            module.canonicalize().context(
+                "Failed to determine canonical path for module entry point",
+            )
+        })
+        .collect::<Result<Vec<_>>>()?;
     Ok(builder
+        .generate_policy(&modules)?
         .load_policy_files(&options.policy)?
-        .fold(module)?
+        .fold(modules)?
         .finalize())
 }
@@ -0,0 +1,69 @@
+//! LavaPack runtime for bundles.
+//!
+//! The runtime from the `@lavamoat/lavapack` package is vendored in
+//! `runtime.js` (see `vendor-runtime.sh`) and is embedded in basalt,
+//! it is used unless a custom runtime file is given.
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+
+use swc_common::{FileName, SourceMap};
+use swc_ecma_ast::Module;
+
+use crate::{
+    module::base::module_package,
+    swc_utils::{load_code, load_file},
+};
+
+/// Version of the vendored `@lavamoat/lavapack` runtime.
+pub const RUNTIME_VERSION: &str = "1.3.4";
+
+const EMBEDDED: &str = include_str!("runtime.js");
+const EMBEDDED_FILE: &str = "@lavamoat/lavapack/src/runtime.js";
+const UNKNOWN_VERSION: &str = "unknown";
+
+/// Load the runtime module.
+pub(crate) fn load(
+    runtime: Option<&Path>,
+    source_map: Arc<SourceMap>,
+) -> Result<Module> {
+    let (_, _, module) = if let Some(file) = runtime {
+        if !file.is_file() {
+            bail!("runtime {} is not a file", file.display());
+        }
+        load_file(file, Some(source_map))?
+    } else {
+        load_code(
+            EMBEDDED,
+            Some(FileName::Custom(EMBEDDED_FILE.into())),
+            Some(source_map),
+        )?
+    };
+    Ok(module)
+}
+
+/// Get the version of the runtime.
+///
+/// The version of a custom runtime is read from the
+/// package.json for the runtime file.
+pub(crate) fn version(runtime: Option<&Path>) -> String {
+    let version = match runtime {
+        Some(file) => file
+            .canonicalize()
+            .ok()
+            .and_then(|file| module_package(&file))
+            .and_then(|package| package.version),
+        None => Some(RUNTIME_VERSION.to_string()),
+    };
+    version.unwrap_or_else(|| UNKNOWN_VERSION.to_string())
+}
+
+/// Comment placed at the start of a bundle.
+pub(crate) fn header(runtime: Option<&Path>) -> String {
+    format!(
+        "// basalt {} (LavaPack runtime {})",
+        env!("CARGO_PKG_VERSION"),
+        version(runtime)
+    )
+}
@@ -0,0 +1,62 @@
+//! Check the policy for a bundle against the bundled modules.
+use anyhow::{bail, Result};
+
+use crate::policy::Policy;
+
+use super::loader::BundlePackages;
+
+/// Remove the resources for packages that are not in the bundle
+/// and check the policy for the packages in the bundle.
+///
+/// A package that imports other packages must have a policy entry
+/// and the packages listed in a policy entry should be imported by
+/// the package. Problems are logged as warnings unless `strict` is
+/// set in which case the first problem is an error.
+pub(super) fn validate_policy(
+    policy: &mut Policy,
+    packages: &BundlePackages,
+    strict: bool,
+) -> Result<()> {
+    policy
+        .resources
+        .retain(|name, _| packages.contains_key(name));
+
+    let mut problems = Vec::new();
+    for (name, imports) in packages {
+        match policy.resources.get(name) {
+            Some(package_policy) => {
+                for dep in package_policy.packages.keys() {
+                    if !imports.contains(dep) {
+                        problems.push(format!(
+                            "policy for package {} allows {} which is not imported",
+                            name, dep
+                        ));
+                    }
+                }
+                for dep in imports {
+                    if !package_policy.packages.keys().any(|k| k == dep) {
+                        problems.push(format!(
+                            "policy for package {} does not allow {}",
+                            name, dep
+                        ));
+                    }
+                }
+            }
+            None if !imports.is_empty() => {
+                problems.push(format!("package {} has no policy entry", name));
+            }
+            None => {}
+        }
+    }
+
+    if strict {
+        if let Some(problem) = problems.into_iter().next() {
+            bail!(problem);
+        }
+    } else {
+        for problem in problems {
+            log::warn!("{}", problem);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,94 @@
+//! Persistent on-disk cache for module analysis.
+//!
+//! Entries are JSON files stored beneath a cache directory and keyed by a
+//! hash of the basalt version and the inputs for the entry (typically the
+//! module contents) so that changing a module or upgrading basalt
+//! invalidates the entry. An entry that cannot be read is treated as a
+//! cache miss.
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use sha1::{Digest, Sha1};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Cache of analysis results stored in a directory.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    /// Create a cache in a directory, the directory is created
+    /// if it does not exist.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir).context(format!(
+            "unable to create cache directory {}",
+            dir.display()
+        ))?;
+        Ok(Self { dir })
+    }
+
+    /// The directory for the cache.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Compute a cache key from the basalt version and the given parts.
+    pub fn key(parts: &[&[u8]]) -> String {
+        let mut hasher = Sha1::new();
+        hasher.update(VERSION.as_bytes());
+        for part in parts {
+            hasher.update(&[0]);
+            hasher.update(part);
+        }
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// Get a cache entry.
+    pub fn get<T: DeserializeOwned>(&self, kind: &str, key: &str) -> Option<T> {
+        let path = self.path(kind, key);
+        let contents = std::fs::read(&path).ok()?;
+        match serde_json::from_slice(&contents) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                log::debug!(
+                    "Ignoring invalid cache entry {} ({})",
+                    path.display(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// Store a cache entry.
+    ///
+    /// The entry is written to a temporary file first so that
+    /// concurrent readers never see a partial entry.
+    pub fn put<T: Serialize>(
+        &self,
+        kind: &str,
+        key: &str,
+        value: &T,
+    ) -> Result<()> {
+        let path = self.path(kind, key);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let temp = path.with_extension(format!(
+            "{}-{:?}.tmp",
+            std::process::id(),
+            std::thread::current().id()
+        ));
+        std::fs::write(&temp, serde_json::to_vec(value)?)?;
+        std::fs::rename(&temp, &path)?;
+        Ok(())
+    }
+
+    fn path(&self, kind: &str, key: &str) -> PathBuf {
+        self.dir.join(kind).join(format!("{}.json", key))
+    }
+}
@@ -5,7 +5,10 @@ use std::path::PathBuf;
 use anyhow::Result;
 use structopt::StructOpt;
 
-use super::{bundle, globals, inspect, meta, parse, policy, transform, tree};
+use super::{
+    bundle, globals, inspect, lint, meta, parse, policy, transform, tree,
+    BundleConfig, SourceMapOptions,
+};
 
 #[derive(StructOpt)]
 enum Debug {
@@ -21,6 +24,15 @@ enum Debug {
 
     /// Parse a dependency graph
     Parse {
+        /// Collect parse and resolve errors and keep going
+        #[structopt(short, long)]
+        keep_going: bool,
+        /// Condition for package exports and imports (repeatable)
+        #[structopt(short = "C", long = "condition", number_of_values = 1)]
+        conditions: Vec<String>,
+        /// Resolve modules using the Yarn Plug'n'Play manifest
+        #[structopt(long)]
+        pnp: bool,
         /// Module entry point
         #[structopt(parse(from_os_str))]
         module: PathBuf,
@@ -50,6 +62,14 @@ enum Debug {
         #[structopt(short, long)]
         json: bool,
 
+        /// Cache module analysis in a directory
+        #[structopt(short, long, parse(from_os_str))]
+        cache_dir: Option<PathBuf>,
+
+        /// Write the source map for the program to a file
+        #[structopt(short, long, parse(from_os_str))]
+        source_map: Option<PathBuf>,
+
         /// Module entry point
         #[structopt(parse(from_os_str))]
         module: PathBuf,
@@ -65,6 +85,18 @@ enum Commands {
         #[structopt(short = "f", long)]
         include_file: bool,
 
+        /// Collect parse and resolve errors and keep going
+        #[structopt(short, long)]
+        keep_going: bool,
+
+        /// Condition for package exports and imports (repeatable)
+        #[structopt(short = "C", long = "condition", number_of_values = 1)]
+        conditions: Vec<String>,
+
+        /// Resolve modules using the Yarn Plug'n'Play manifest
+        #[structopt(long)]
+        pnp: bool,
+
         /// Module entry point
         #[structopt(parse(from_os_str))]
         module: PathBuf,
@@ -72,25 +104,92 @@ enum Commands {
 
     /// Generate a lavapack bundle
     Bundle {
-        /// Path to policy file(s)
+        /// Path to policy override file(s)
         #[structopt(short, long)]
         policy: Vec<PathBuf>,
-        /// Source map destination
+        /// Write the policy embedded in the bundle to a file
+        #[structopt(long, parse(from_os_str))]
+        policy_output: Option<PathBuf>,
+        /// Fail when the policy does not match the bundled modules
+        #[structopt(long)]
+        strict_policy: bool,
+        /// Source map destination or `inline` to embed the source map
         #[structopt(short, long)]
         source_map: Option<PathBuf>,
         /// Source map URL
         #[structopt(short = "u", long)]
         source_map_url: Option<String>,
+        /// Include the contents of source files in the source map
+        #[structopt(long)]
+        sources_content: bool,
+        /// Make source file paths in the source map relative to a directory
+        #[structopt(long, parse(from_os_str))]
+        source_root: Option<PathBuf>,
         /// Write bundle to output
         #[structopt(short, long)]
         output: Option<PathBuf>,
-        /// Bundle entry point
-        #[structopt(parse(from_os_str))]
-        module: PathBuf,
+        /// Collect parse and resolve errors and keep going
+        #[structopt(short, long)]
+        keep_going: bool,
+        /// Cache module analysis in a directory
+        #[structopt(short, long, parse(from_os_str))]
+        cache_dir: Option<PathBuf>,
+        /// Watch for changes and rebuild
+        #[structopt(short, long)]
+        watch: bool,
+        /// Condition for package exports and imports (repeatable)
+        #[structopt(short = "C", long = "condition", number_of_values = 1)]
+        conditions: Vec<String>,
+        /// Resolve modules using the Yarn Plug'n'Play manifest
+        #[structopt(long)]
+        pnp: bool,
+        /// Use a custom LavaPack runtime file
+        #[structopt(short, long, parse(from_os_str))]
+        runtime: Option<PathBuf>,
+        /// Minify the bundle
+        #[structopt(short, long)]
+        minify: bool,
+        /// Bundle entry point(s)
+        #[structopt(parse(from_os_str), required = true)]
+        modules: Vec<PathBuf>,
     },
 
     /// Generate a lavamoat policy file
     Policy {
+        /// Write debug information to file
+        #[structopt(short, long)]
+        debug_file: Option<PathBuf>,
+        /// Collect parse and resolve errors and keep going
+        #[structopt(short, long)]
+        keep_going: bool,
+        /// Cache module analysis in a directory
+        #[structopt(short, long, parse(from_os_str))]
+        cache_dir: Option<PathBuf>,
+        /// Watch for changes and rebuild
+        #[structopt(short, long)]
+        watch: bool,
+        /// Condition for package exports and imports (repeatable)
+        #[structopt(short = "C", long = "condition", number_of_values = 1)]
+        conditions: Vec<String>,
+        /// Resolve modules using the Yarn Plug'n'Play manifest
+        #[structopt(long)]
+        pnp: bool,
+        /// Module entry point
+        #[structopt(parse(from_os_str))]
+        module: PathBuf,
+    },
+
+    /// Lint a module and its dependencies
+    Lint {
+        /// Collect parse and resolve errors and keep going
+        #[structopt(short, long)]
+        keep_going: bool,
+        /// Condition for package exports and imports (repeatable)
+        #[structopt(short = "C", long = "condition", number_of_values = 1)]
+        conditions: Vec<String>,
+        /// Resolve modules using the Yarn Plug'n'Play manifest
+        #[structopt(long)]
+        pnp: bool,
         /// Module entry point
         #[structopt(parse(from_os_str))]
         module: PathBuf,
@@ -124,24 +223,83 @@ where
         Commands::Tree {
             module,
             include_file,
+            keep_going,
+            conditions,
+            pnp,
         } => {
-            tree(module, include_file)?;
+            tree(module, include_file, keep_going, conditions, pnp)?;
         }
         Commands::Bundle {
-            module,
+            modules,
             policy,
+            policy_output,
+            strict_policy,
             output,
             source_map,
             source_map_url,
-        } => bundle(module, policy, output, source_map, source_map_url)?,
+            sources_content,
+            source_root,
+            keep_going,
+            cache_dir,
+            watch,
+            conditions,
+            pnp,
+            runtime,
+            minify,
+        } => bundle(BundleConfig {
+            modules,
+            policy,
+            policy_output,
+            strict_policy,
+            output,
+            source_map: SourceMapOptions {
+                path: source_map,
+                url: source_map_url,
+                sources_content,
+                root: source_root,
+            },
+            keep_going,
+            cache_dir,
+            watch,
+            conditions,
+            pnp,
+            runtime,
+            minify,
+        })?,
 
-        Commands::Policy { module } => policy(module)?,
+        Commands::Policy {
+            module,
+            debug_file,
+            keep_going,
+            cache_dir,
+            watch,
+            conditions,
+            pnp,
+        } => policy(
+            module, debug_file, keep_going, cache_dir, watch, conditions, pnp,
+        )?,
+        Commands::Lint {
+            module,
+            keep_going,
+            conditions,
+            pnp,
+        } => lint(module, keep_going, conditions, pnp)?,
         Commands::Debug { cmd } => match cmd {
             Debug::Inspect { code, module } => inspect(code, module)?,
-            Debug::Parse { module } => parse(module)?,
+            Debug::Parse {
+                module,
+                keep_going,
+                conditions,
+                pnp,
+            } => parse(module, keep_going, conditions, pnp)?,
             Debug::Globals { module, debug } => globals(module, debug)?,
             Debug::Meta { module } => meta(module)?,
-            Debug::Transform { module, json } => transform(module, json)?,
+            Debug::Transform {
+                module,
+                json,
+                cache_dir,
+                source_map,
+            } => transform(module, json, cache_dir, source_map)?,
         },
     }
     Ok(())
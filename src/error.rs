@@ -0,0 +1,127 @@
+//! Structured errors for modules that fail to parse or resolve.
+//!
+//! By default the first error aborts the command; when keep going
+//! is enabled for a module graph parse and resolve errors are collected
+//! so that the remainder of the graph can still be processed and the
+//! errors reported as a summary once the command has finished.
+use std::fmt;
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+use swc_common::{FileName, SourceMap, Span};
+
+/// Error for a module that could not be parsed or resolved.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ModuleError {
+    /// Module could not be parsed.
+    Parse {
+        /// The module file.
+        file: String,
+        /// The line number (one-based).
+        line: usize,
+        /// The column number (one-based).
+        column: usize,
+        /// The parser error message.
+        message: String,
+    },
+    /// Dependency of a module could not be resolved.
+    Resolve {
+        /// The module file that declares the dependency.
+        file: String,
+        /// The dependency specifier.
+        specifier: String,
+        /// The line number (one-based).
+        line: usize,
+        /// The column number (one-based).
+        column: usize,
+        /// The resolver error message.
+        message: String,
+    },
+}
+
+impl ModuleError {
+    /// Create a parse error for a location in a module.
+    pub fn parse(
+        file_name: &FileName,
+        source_map: &SourceMap,
+        span: Span,
+        message: String,
+    ) -> Self {
+        let (line, column) = location(source_map, span);
+        ModuleError::Parse {
+            file: file_name.to_string(),
+            line,
+            column,
+            message,
+        }
+    }
+
+    /// Create a resolve error for a dependency of a module.
+    pub fn resolve(
+        file_name: &FileName,
+        source_map: &SourceMap,
+        span: Span,
+        specifier: String,
+        message: String,
+    ) -> Self {
+        let (line, column) = location(source_map, span);
+        ModuleError::Resolve {
+            file: file_name.to_string(),
+            specifier,
+            line,
+            column,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModuleError::Parse {
+                file,
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "{}:{}:{}: failed to parse module: {}",
+                file, line, column, message
+            ),
+            ModuleError::Resolve {
+                file,
+                specifier,
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "{}:{}:{}: failed to resolve {}: {}",
+                file, line, column, specifier, message
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ModuleError {}
+
+fn location(source_map: &SourceMap, span: Span) -> (usize, usize) {
+    let loc = source_map.lookup_char_pos(span.lo);
+    (loc.line, loc.col.0 + 1)
+}
+
+/// Print a summary of the collected errors.
+///
+/// Returns an error when any errors were collected.
+pub fn summary(errors: Vec<ModuleError>) -> Result<()> {
+    if errors.is_empty() {
+        return Ok(());
+    }
+    eprintln!();
+    for error in errors.iter() {
+        eprintln!("{}", error);
+    }
+    bail!("{} module error(s) were skipped", errors.len());
+}
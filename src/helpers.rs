@@ -1,4 +1,6 @@
 //! Collection of helper functions.
+use std::path::{Component, Path};
+
 use swc_atoms::JsWord;
 use swc_ecma_ast::*;
 
@@ -150,3 +152,28 @@ pub fn is_module_exports(n: &PatOrExpr) -> bool {
     }
     false
 }
+
+/// Get the path from a base directory to a path using forward slashes.
+///
+/// Both paths should be absolute, parent directory components are
+/// used when the path is not inside the base directory.
+pub fn relative_path(base: &Path, path: &Path) -> String {
+    let path_components: Vec<Component> = path.components().collect();
+    let base_components: Vec<Component> = base.components().collect();
+    let common = path_components
+        .iter()
+        .zip(base_components.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut parts: Vec<String> = base_components[common..]
+        .iter()
+        .map(|_| "..".to_string())
+        .collect();
+    parts.extend(
+        path_components[common..]
+            .iter()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned()),
+    );
+    parts.join("/")
+}
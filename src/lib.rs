@@ -4,8 +4,8 @@
 #![feature(once_cell)]
 #![deny(missing_docs)]
 
-use std::fs::OpenOptions;
-use std::io::{self, prelude::*, Read};
+use std::collections::HashSet;
+use std::io::{self, Read};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::SystemTime;
@@ -14,23 +14,119 @@ use anyhow::{bail, Context, Result};
 
 use swc::config::SourceMapsConfig;
 use swc_common::SourceMap;
+use swc_ecma_ast::Program;
 use swc_ecma_visit::VisitWith;
 
 pub mod access;
 pub mod bundler;
+pub mod cache;
 pub mod cli;
+pub mod error;
 pub mod helpers;
+pub mod lint;
 pub mod module;
 pub mod policy;
 pub mod printer;
 pub mod static_module_record;
 pub mod swc_utils;
+pub mod watch;
 
 pub use static_module_record::{
     Parser, StaticModuleRecordProgram, TransformSource,
 };
 
-use policy::{analysis::globals_scope::GlobalAnalysis, builder::PolicyBuilder};
+use cache::Cache;
+use helpers::relative_path;
+use module::{
+    graph::ModuleGraph,
+    kind::declared_kind,
+    pnp::PnpResolver,
+    resolver::{IMPORT_CONDITIONS, REQUIRE_CONDITIONS},
+};
+use policy::{
+    analysis::globals_scope::GlobalAnalysis, builder::PolicyBuilder,
+    debug::ModuleSuppression, Policy,
+};
+
+/// Cache entry kind for transformed programs.
+const TRANSFORM: &str = "transform";
+
+/// Source map destination that embeds the source map in the bundle.
+const INLINE_SOURCE_MAP: &str = "inline";
+
+/// Options for the source map of a bundle.
+#[derive(Debug, Default)]
+pub struct SourceMapOptions {
+    /// Source map destination or `inline` to embed the source map.
+    pub path: Option<PathBuf>,
+    /// URL for the source map, the default is the path to
+    /// the source map relative to the bundle.
+    pub url: Option<String>,
+    /// Include the contents of the source files.
+    pub sources_content: bool,
+    /// Directory that paths to source files are relative to, the
+    /// default is the directory for the source map.
+    pub root: Option<PathBuf>,
+}
+
+/// Options for generating a bundle.
+#[derive(Debug, Default)]
+pub struct BundleConfig {
+    /// Bundle entry points.
+    pub modules: Vec<PathBuf>,
+    /// Policy override files.
+    pub policy: Vec<PathBuf>,
+    /// Write the policy embedded in the bundle to a file.
+    pub policy_output: Option<PathBuf>,
+    /// Fail when the policy does not match the bundled modules.
+    pub strict_policy: bool,
+    /// Write the bundle to a file rather than stdout.
+    pub output: Option<PathBuf>,
+    /// Options for the source map.
+    pub source_map: SourceMapOptions,
+    /// Collect parse and resolve errors and keep going.
+    pub keep_going: bool,
+    /// Cache module analysis in a directory.
+    pub cache_dir: Option<PathBuf>,
+    /// Watch for changes and rebuild.
+    pub watch: bool,
+    /// Conditions for package exports and imports.
+    pub conditions: Vec<String>,
+    /// Resolve modules using the Yarn Plug'n'Play manifest.
+    pub pnp: bool,
+    /// Use a custom LavaPack runtime file.
+    pub runtime: Option<PathBuf>,
+    /// Minify the bundle.
+    pub minify: bool,
+}
+
+/// Use the default conditions for package exports and imports
+/// when no conditions are given.
+fn with_defaults(conditions: Vec<String>, defaults: &[&str]) -> Vec<String> {
+    if conditions.is_empty() {
+        defaults.iter().map(|c| c.to_string()).collect()
+    } else {
+        conditions
+    }
+}
+
+/// Create a module graph for an entry point.
+///
+/// When `pnp` is set packages are resolved using the
+/// Plug'n'Play manifest for the project.
+fn module_graph(
+    file: &Path,
+    conditions: Vec<String>,
+    pnp: bool,
+    cache: Option<&Cache>,
+) -> Result<ModuleGraph> {
+    let graph = if pnp {
+        ModuleGraph::with_pnp(PnpResolver::find(file)?, conditions, cache)?
+    } else {
+        ModuleGraph::with_conditions(conditions)
+    };
+    Ok(graph.cache(cache.cloned()))
+}
 
 /// Write a file and create the parent directory when necessary.
 fn write_file<P: AsRef<Path>, C: AsRef<[u8]>>(
@@ -49,80 +145,219 @@ fn write_file<P: AsRef<Path>, C: AsRef<[u8]>>(
     Ok(())
 }
 
-/// Append sourceMappingURL to a target file.
-fn append_source_mapping_url<P: AsRef<Path>>(path: P, url: &str) -> Result<()> {
-    let url = format!("//# sourceMappingURL={}", url);
-    let mut file = OpenOptions::new()
-        .write(true)
-        .append(true)
-        .open(path.as_ref())
-        .unwrap();
+/// Generate a bundle.
+///
+/// The policy embedded in the bundle is generated from the modules
+/// in the bundle and the policy files are merged as overrides; the
+/// resources for packages that are not in the bundle are removed.
+///
+/// Modules shared by multiple entry points are only included once
+/// and every entry point is executed when the bundle is loaded.
+///
+/// When watching the bundle is written again whenever a module in
+/// the graph or a policy file changes.
+pub fn bundle(config: BundleConfig) -> Result<()> {
+    let BundleConfig {
+        modules,
+        policy,
+        policy_output,
+        strict_policy,
+        output,
+        source_map,
+        keep_going,
+        cache_dir,
+        watch,
+        conditions,
+        pnp,
+        runtime,
+        minify,
+    } = config;
+
+    if modules.is_empty() {
+        bail!("at least one entry point is required to bundle");
+    }
 
-    if let Err(e) = writeln!(file, "{}", &url) {
-        bail!(
-            "failed to append sourceMappingURL to file {}, reason: {}",
-            path.as_ref().display(),
-            e
-        );
+    let modules = modules
+        .iter()
+        .map(|module| {
+            module.canonicalize().context(format!(
+                "unable to get canonical path for {}",
+                module.display()
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let options = bundler::BundleOptions {
+        policy,
+        keep_going,
+        cache: cache_dir.map(Cache::new).transpose()?,
+        conditions: with_defaults(conditions, IMPORT_CONDITIONS),
+        pnp: if pnp {
+            Some(PnpResolver::find(&modules[0])?)
+        } else {
+            None
+        },
+        runtime,
+        strict_policy,
+        minify,
+        modules,
+    };
+    let header = bundler::header(&options);
+
+    if !watch {
+        let (program, analysis, policy) = bundler::bundle(options)?;
+        let graph = analysis.graph();
+        write_bundle_policy(&policy, &policy_output)?;
+        write_bundle(&program, graph, &header, &output, &source_map, minify)?;
+        return error::summary(graph.take_errors());
     }
 
-    Ok(())
+    let mut files: HashSet<PathBuf> = options.policy.iter().cloned().collect();
+    files.extend(options.modules.iter().cloned());
+
+    // The policy builder keeps the module graph and the analysis
+    // for each module between builds
+    let mut analysis: Option<PolicyBuilder> = None;
+    watch::watch(files, |changed| {
+        // The builder is dropped when a build fails so the
+        // next build starts with a new graph
+        let current = match analysis.take() {
+            Some(analysis) => analysis,
+            None => bundler::policy_builder(&options)?,
+        };
+        let current = current.invalidate(changed);
+
+        let (program, current, policy) =
+            bundler::bundle_with(&options, current)?;
+        let graph = current.graph();
+        write_bundle_policy(&policy, &policy_output)?;
+        write_bundle(&program, graph, &header, &output, &source_map, minify)?;
+
+        let mut files = graph.files();
+        files.extend(options.policy.iter().cloned());
+        if let Err(e) = error::summary(graph.take_errors()) {
+            log::error!("{}", e);
+        }
+        analysis = Some(current);
+        Ok(files)
+    })
 }
 
-/// Generate a bundle.
-pub fn bundle(
-    module: PathBuf,
-    policy: Vec<PathBuf>,
-    output: Option<PathBuf>,
-    source_map_path: Option<PathBuf>,
-    source_map_url: Option<String>,
+/// Print a bundle program and write the output and source map files.
+fn write_bundle(
+    program: &Program,
+    graph: &ModuleGraph,
+    header: &str,
+    output: &Option<PathBuf>,
+    options: &SourceMapOptions,
+    minify: bool,
 ) -> Result<()> {
-    if policy.is_empty() {
-        bail!("bundle command requires some policy file(s) (use --policy)");
-    }
-
-    let module = module.canonicalize().context(format!(
-        "unable to get canonical path for {}",
-        module.display()
-    ))?;
+    let source_map = Arc::clone(graph.source_map());
+    let source_maps_config = SourceMapsConfig::Bool(options.path.is_some());
+    let result = swc_utils::print(
+        program,
+        source_map,
+        None,
+        None,
+        options.sources_content,
+        source_maps_config,
+        minify,
+    )?;
+    let mut code = format!("{}\n{}", header, result.code);
+
+    if let (Some(path), Some(contents)) = (&options.path, result.map) {
+        let inline = path.as_os_str() == INLINE_SOURCE_MAP;
+
+        // Sources are relative to the source map unless a root is given
+        let map_dir = if inline {
+            output.as_ref().and_then(|p| p.parent())
+        } else {
+            path.parent()
+        };
+        let root = match &options.root {
+            Some(root) => absolute_path(root)?,
+            None => absolute_path(map_dir.unwrap_or_else(|| Path::new("")))?,
+        };
+        let contents =
+            rewrite_source_map(&contents, header.lines().count(), &root)?;
+
+        let url = if inline {
+            Some(format!(
+                "data:application/json;charset=utf-8;base64,{}",
+                base64::encode(contents)
+            ))
+        } else {
+            write_file(path, contents)?;
+            match (&options.url, output) {
+                (Some(url), _) => Some(url.clone()),
+                (None, Some(output)) => {
+                    let output_dir = absolute_path(
+                        output.parent().unwrap_or_else(|| Path::new("")),
+                    )?;
+                    Some(relative_path(&output_dir, &absolute_path(path)?))
+                }
+                (None, None) => None,
+            }
+        };
 
-    let options = bundler::BundleOptions { module, policy };
-    let (program, source_map) = bundler::bundle(options)?;
-    let source_maps_config = SourceMapsConfig::Bool(true);
-    let result =
-        swc_utils::print(&program, source_map, None, None, source_maps_config)?;
+        if let Some(url) = url {
+            if !code.ends_with('\n') {
+                code.push('\n');
+            }
+            code.push_str(&format!("//# sourceMappingURL={}\n", url));
+        }
+    }
 
-    if let Some(path) = &output {
-        write_file(path, result.code)?;
+    if let Some(path) = output {
+        write_file(path, code)?;
     } else {
-        println!("{}", result.code);
+        println!("{}", code);
     }
+    Ok(())
+}
 
-    // Write out the source map file
-    if let (Some(path), Some(contents)) = (source_map_path, result.map) {
-        write_file(&path, contents)?;
-
-        // Handle appending sourceMappingURL to bundle file
-        if let Some(output_path) = &output {
-            let url = if let Some(url) = source_map_url {
-                Some(url)
-            } else {
-                // FIXME: do not assume same directory by default,
-                // FIXME: try to create relative path
-                if let Some(file_name) = path.file_name() {
-                    Some(file_name.to_string_lossy().into_owned())
-                } else {
-                    None
-                }
-            };
+/// Write the policy embedded in a bundle.
+fn write_bundle_policy(
+    policy: &Policy,
+    output: &Option<PathBuf>,
+) -> Result<()> {
+    if let Some(path) = output {
+        write_file(path, serde_json::to_string_pretty(policy)?)?;
+    }
+    Ok(())
+}
 
-            if let Some(url) = &url {
-                append_source_mapping_url(output_path, url)?;
+/// Shift the mappings in a source map down by a number of lines to
+/// account for lines inserted before the code and make the paths to
+/// source files relative to a root directory.
+fn rewrite_source_map(
+    contents: &str,
+    lines: usize,
+    root: &Path,
+) -> Result<String> {
+    let mut map: serde_json::Value = serde_json::from_str(contents)?;
+    if let Some(serde_json::Value::String(mappings)) = map.get_mut("mappings") {
+        mappings.insert_str(0, &";".repeat(lines));
+    }
+    if let Some(serde_json::Value::Array(sources)) = map.get_mut("sources") {
+        for source in sources.iter_mut() {
+            if let serde_json::Value::String(file) = source {
+                let path = Path::new(file.as_str());
+                if path.is_absolute() {
+                    *file = relative_path(root, path);
+                }
             }
         }
     }
+    Ok(serde_json::to_string(&map)?)
+}
 
-    Ok(())
+/// Get an absolute path for a file that may not exist yet.
+fn absolute_path(path: &Path) -> Result<PathBuf> {
+    match path.canonicalize() {
+        Ok(path) => Ok(path),
+        Err(_) => Ok(std::env::current_dir()?.join(path)),
+    }
 }
 
 /// Inspect the AST for a string or file.
@@ -143,40 +378,158 @@ pub fn inspect(code: Option<String>, file: Option<PathBuf>) -> Result<()> {
     Ok(())
 }
 
+/// Lint all the modules in a dependency graph.
+pub fn lint(
+    file: PathBuf,
+    keep_going: bool,
+    conditions: Vec<String>,
+    pnp: bool,
+) -> Result<()> {
+    if !file.is_file() {
+        bail!("module {} does not exist or is not a file", file.display());
+    }
+
+    let conditions = with_defaults(conditions, REQUIRE_CONDITIONS);
+    let graph =
+        module_graph(&file, conditions, pnp, None)?.keep_going(keep_going);
+    let report = lint::lint(&graph, file)?;
+    for warning in report.warnings.iter() {
+        println!("{}", warning);
+    }
+    for ModuleSuppression { file, suppressed } in report.suppressions.iter() {
+        for line in suppressed.lines.iter() {
+            log::info!(
+                "{}:{}: suppressed {} [{}]",
+                file.display(),
+                line,
+                suppressed.symbol,
+                suppressed.rule
+            );
+        }
+    }
+
+    error::summary(graph.take_errors())?;
+
+    if !report.warnings.is_empty() {
+        bail!("found {} lint warning(s)", report.warnings.len());
+    }
+
+    Ok(())
+}
+
 /// Parse all the modules in a dependency graph.
-pub fn parse(file: PathBuf) -> Result<()> {
+pub fn parse(
+    file: PathBuf,
+    keep_going: bool,
+    conditions: Vec<String>,
+    pnp: bool,
+) -> Result<()> {
+    let conditions = with_defaults(conditions, REQUIRE_CONDITIONS);
+    let graph =
+        module_graph(&file, conditions, pnp, None)?.keep_going(keep_going);
     let now = SystemTime::now();
-    let (parsed_modules, visited_modules) = module::parser::parse(file)?;
+    let (parsed_modules, visited_modules) =
+        module::parser::parse(&graph, file)?;
     if let Ok(t) = now.elapsed() {
         log::debug!("Visited {} module(s)", visited_modules);
         log::info!("Parsed {} module(s) in {:?}", parsed_modules, t);
     }
-    Ok(())
+    error::summary(graph.take_errors())
 }
 
 /// Generate a policy file.
-pub fn policy(file: PathBuf) -> Result<()> {
+///
+/// When a debug file is given the debug information collected
+/// during analysis is written to the file.
+///
+/// When watching the policy is generated again whenever a module
+/// in the graph changes.
+pub fn policy(
+    file: PathBuf,
+    debug_file: Option<PathBuf>,
+    keep_going: bool,
+    cache_dir: Option<PathBuf>,
+    watch: bool,
+    conditions: Vec<String>,
+    pnp: bool,
+) -> Result<()> {
     if !file.is_file() {
         bail!("module {} does not exist or is not a file", file.display());
     }
 
-    let builder = PolicyBuilder::new(file);
-    let policy = builder.load()?.analyze()?.finalize();
-    let policy_content = serde_json::to_string_pretty(&policy)?;
-    println!("{}", policy_content);
+    let cache = cache_dir.map(Cache::new).transpose()?;
+    let conditions = with_defaults(conditions, REQUIRE_CONDITIONS);
+    let new_builder = |file: PathBuf| -> Result<PolicyBuilder> {
+        let graph =
+            module_graph(&file, conditions.clone(), pnp, cache.as_ref())?
+                .keep_going(keep_going);
+        Ok(PolicyBuilder::with_graph(file, graph))
+    };
 
+    if !watch {
+        let builder = new_builder(file)?.load()?.analyze()?;
+        write_policy(&builder, &debug_file)?;
+        return error::summary(builder.graph().take_errors());
+    }
+
+    // Use the canonical path so the entry point matches
+    // the paths reported for file changes
+    let file = file.canonicalize()?;
+    let mut files = HashSet::new();
+    files.insert(file.clone());
+
+    let mut builder: Option<PolicyBuilder> = None;
+    watch::watch(files, |changed| {
+        // The builder is dropped when a build fails so the
+        // next build starts with a new graph
+        let current = match builder.take() {
+            Some(builder) => builder.invalidate(changed),
+            None => new_builder(file.clone())?,
+        };
+        let current = current.load()?.analyze()?;
+        write_policy(&current, &debug_file)?;
+
+        let files = current.graph().files();
+        if let Err(e) = error::summary(current.graph().take_errors()) {
+            log::error!("{}", e);
+        }
+        builder = Some(current);
+        Ok(files)
+    })
+}
+
+/// Print a policy and write the debug file.
+fn write_policy(
+    builder: &PolicyBuilder,
+    debug_file: &Option<PathBuf>,
+) -> Result<()> {
+    if let Some(debug_file) = debug_file {
+        let debug_content = serde_json::to_string_pretty(builder.debug())?;
+        write_file(debug_file, debug_content)?;
+    }
+    let policy_content = serde_json::to_string_pretty(builder.policy())?;
+    println!("{}", policy_content);
     Ok(())
 }
 
 /// Print the dependency graph as a tree.
-pub fn tree(file: PathBuf, include_file: bool) -> Result<()> {
+pub fn tree(
+    file: PathBuf,
+    include_file: bool,
+    keep_going: bool,
+    conditions: Vec<String>,
+    pnp: bool,
+) -> Result<()> {
     if !file.is_file() {
         bail!("module {} does not exist or is not a file", file.display());
     }
+    let conditions = with_defaults(conditions, REQUIRE_CONDITIONS);
+    let graph =
+        module_graph(&file, conditions, pnp, None)?.keep_going(keep_going);
     let options = printer::PrintOptions { include_file };
     let printer = printer::Printer::new();
-    printer.print(file, &options)?;
-    Ok(())
+    printer.print(&graph, file, &options)?;
+    error::summary(graph.take_errors())
 }
 
 /// Print the static module record meta data as JSON.
@@ -217,7 +570,17 @@ pub fn globals(file: PathBuf, debug: bool) -> Result<()> {
 }
 
 /// Transform a module to a static module record program.
-pub fn transform(file: PathBuf, json: bool) -> Result<()> {
+///
+/// When a cache directory is given the transformed program for a file
+/// is cached using the file path, its declared kind and the contents.
+/// The source map for the program
+/// is included in the JSON output and can be written to a file.
+pub fn transform(
+    file: PathBuf,
+    json: bool,
+    cache_dir: Option<PathBuf>,
+    source_map_path: Option<PathBuf>,
+) -> Result<()> {
     let is_stdin = PathBuf::from("-") == file;
     if !file.is_file() && !is_stdin {
         bail!("module {} does not exist or is not a file", file.display());
@@ -235,16 +598,54 @@ pub fn transform(file: PathBuf, json: bool) -> Result<()> {
         TransformSource::File(file)
     };
 
-    let source_map: Arc<SourceMap> = Arc::new(Default::default());
-    let (meta, result) = static_module_record::transform(source, source_map)?;
-    if json {
+    // The source map names the file and the declared kind decides
+    // whether the module can be transformed so both are in the key
+    let cache = cache_dir.map(Cache::new).transpose()?;
+    let key = match (&cache, &source) {
+        (Some(_), TransformSource::File(path)) => Some(Cache::key(&[
+            TRANSFORM.as_bytes(),
+            path.to_string_lossy().as_bytes(),
+            format!("{:?}", declared_kind(path)).as_bytes(),
+            &std::fs::read(path)?,
+        ])),
+        _ => None,
+    };
+
+    let cached = cache
+        .as_ref()
+        .zip(key.as_ref())
+        .and_then(|(cache, key)| cache.get(TRANSFORM, key));
+    let output: StaticModuleRecordProgram = if let Some(output) = cached {
+        output
+    } else {
+        let source_map: Arc<SourceMap> = Arc::new(Default::default());
+        let (meta, result) =
+            static_module_record::transform(source, source_map)?;
         let output = StaticModuleRecordProgram {
             meta,
-            program: trim_code(result.code),
+            program: result.code,
+            source_map: result.map,
+        };
+        if let (Some(cache), Some(key)) = (&cache, &key) {
+            cache.put(TRANSFORM, key, &output)?;
+        }
+        output
+    };
+
+    if let (Some(path), Some(contents)) = (&source_map_path, &output.source_map)
+    {
+        write_file(path, contents)?;
+    }
+
+    if json {
+        let output = StaticModuleRecordProgram {
+            meta: output.meta,
+            program: trim_code(output.program),
+            source_map: output.source_map,
         };
         print!("{}", serde_json::to_string_pretty(&output)?);
     } else {
-        print!("{}", result.code);
+        print!("{}", output.program);
     }
     Ok(())
 }
@@ -0,0 +1,127 @@
+//! Lint the modules in a dependency graph.
+//!
+//! Warnings may be suppressed using inline comment directives
+//! that name the rule, for example:
+//!
+//! ```javascript
+//! // basalt-ignore-next-line dynamic-import
+//! const locale = require('./locale/' + lang);
+//! ```
+//!
+//! Suppressed warnings are recorded in the lint report.
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use swc_common::FileName;
+
+use crate::{
+    module::{
+        graph::ModuleGraph,
+        node::{ModuleNode, VisitedDependency, VisitedModule},
+    },
+    policy::{
+        analysis::suppression::{Rule, Suppressed},
+        debug::ModuleSuppression,
+    },
+};
+
+/// Rule for calls to `require()` or `import()` that cannot
+/// be resolved statically.
+pub const DYNAMIC_IMPORT: &str = "dynamic-import";
+
+/// Lint warning for a module.
+#[derive(Debug, Serialize)]
+pub struct Warning {
+    /// The lint rule.
+    pub rule: &'static str,
+    /// The module file.
+    pub file: PathBuf,
+    /// The line number (one-based).
+    pub line: usize,
+    /// The warning message.
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {} [{}]",
+            self.file.display(),
+            self.line,
+            self.message,
+            self.rule
+        )
+    }
+}
+
+/// Warnings and applied suppressions for a module graph.
+#[derive(Debug, Default, Serialize)]
+pub struct LintReport {
+    /// The lint warnings.
+    pub warnings: Vec<Warning>,
+    /// Inline suppressions applied to lint warnings.
+    pub suppressions: Vec<ModuleSuppression>,
+}
+
+/// Lint a module and all of its dependencies.
+pub fn lint<P: AsRef<Path>>(
+    graph: &ModuleGraph,
+    file: P,
+) -> Result<LintReport> {
+    let module = graph.load(file.as_ref())?;
+
+    let mut report: LintReport = Default::default();
+
+    let node = match &*module {
+        VisitedModule::Module(file_name, node) => {
+            if let FileName::Real(path) = file_name {
+                lint_module(path, node, &mut report);
+            }
+            Some(node)
+        }
+        VisitedModule::Json(_, node) => Some(node),
+        VisitedModule::Builtin(_) => None,
+    };
+
+    let mut visitor = |dep: VisitedDependency| {
+        if let (FileName::Real(path), Some(node)) = (&dep.file_name, dep.node) {
+            lint_module(path, node, &mut report);
+        }
+        Ok(())
+    };
+
+    if let Some(node) = node {
+        node.walk(graph, &mut visitor)?;
+    }
+
+    Ok(report)
+}
+
+fn lint_module(file: &Path, node: &ModuleNode, report: &mut LintReport) {
+    let rule = Rule::Lint(DYNAMIC_IMPORT.to_string());
+    for import in node.dynamic_imports.iter() {
+        if let Some(directive_rule) =
+            node.suppressions.find(&rule, &import.span)
+        {
+            report.suppressions.push(ModuleSuppression {
+                file: file.to_path_buf(),
+                suppressed: Suppressed {
+                    rule: directive_rule.clone(),
+                    symbol: DYNAMIC_IMPORT.to_string(),
+                    lines: vec![import.line],
+                },
+            });
+        } else {
+            report.warnings.push(Warning {
+                rule: DYNAMIC_IMPORT,
+                file: file.to_path_buf(),
+                line: import.line,
+                message: import.to_string(),
+            });
+        }
+    }
+}
@@ -1,17 +1,79 @@
-//! Helper to determine the base path for a module.
-use std::path::PathBuf;
+//! Helper to determine the base path and package for a module.
+//!
+//! Packages are identified by the nearest package.json that declares a
+//! `name` so that nested package.json files which only configure a
+//! directory (for example `{"type": "module"}`) do not split a package
+//! and packages linked from a workspace are identified by name rather
+//! than by where they are located. A package installed in `node_modules`
+//! that does not declare a name is named by its install location.
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
 
 const PACKAGE: &str = "package.json";
+const NODE_MODULES: &str = "node_modules";
 
-/// Attempt to find the base directory for a module using the resolved path for the module.
-pub fn module_base_directory(path: &PathBuf) -> Option<PathBuf> {
+#[derive(Deserialize)]
+struct PackageJson {
+    name: Option<String>,
+    version: Option<String>,
+}
+
+/// Package that contains a module.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ModulePackage {
+    /// The directory containing the package.json file.
+    pub base: PathBuf,
+    /// The package name.
+    pub name: String,
+    /// The package version.
+    pub version: Option<String>,
+}
+
+/// Attempt to find the package for a module using the resolved path for the module.
+pub fn module_package(path: &Path) -> Option<ModulePackage> {
     let mut parent = path.parent();
     while let Some(p) = parent {
         let pkg = p.join(PACKAGE);
         if pkg.is_file() {
-            return Some(p.to_path_buf());
+            let json = std::fs::read_to_string(&pkg)
+                .ok()
+                .and_then(|s| serde_json::from_str::<PackageJson>(&s).ok());
+            let (name, version) = match json {
+                Some(json) => (json.name, json.version),
+                None => (None, None),
+            };
+            if let Some(name) = name.or_else(|| installed_name(p)) {
+                return Some(ModulePackage {
+                    base: p.to_path_buf(),
+                    name,
+                    version,
+                });
+            }
         }
         parent = p.parent();
     }
     None
 }
+
+/// Attempt to find the base directory for a module using the resolved path for the module.
+pub fn module_base_directory(path: &PathBuf) -> Option<PathBuf> {
+    module_package(path).map(|package| package.base)
+}
+
+/// Determine the name of a package from the `node_modules` directory
+/// it is installed in.
+fn installed_name(dir: &Path) -> Option<String> {
+    let name = dir.file_name()?.to_str()?;
+    let parent = dir.parent()?;
+    let parent_name = parent.file_name()?.to_str()?;
+    if parent_name == NODE_MODULES {
+        Some(name.to_string())
+    } else if parent_name.starts_with('@')
+        && parent.parent()?.file_name()? == NODE_MODULES
+    {
+        Some(format!("{}/{}", parent_name, name))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,606 @@
+//! Module graph that owns the parsed modules for an analysis.
+//!
+//! Each graph has its own module cache, module identifiers, resolver
+//! and source map so that multiple analyses in the same process are
+//! isolated from each other and the modules are released when the
+//! graph is dropped.
+//!
+//! Loading a graph parses and resolves modules concurrently using the
+//! rayon thread pool so that subsequent visits of the graph are served
+//! from the cache.
+//!
+//! When an analysis cache is assigned the resolved dependencies for each
+//! module are stored on disk and modules that have not changed are not
+//! parsed on subsequent runs; call `reparse()` when the module AST is
+//! required. The resolved dependencies are stale when a package.json or
+//! `tsconfig.json` consulted by the resolver or the Plug'n'Play manifest
+//! has changed.
+//!
+//! Modules for files that have changed can be removed with `invalidate()`
+//! so that loading the graph again only parses the changed modules.
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{
+    atomic::{AtomicU32, Ordering::SeqCst},
+    Arc, Mutex,
+};
+
+use anyhow::Result;
+use dashmap::DashMap;
+use indexmap::IndexSet;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use swc_common::{comments::SingleThreadedComments, FileName, SourceMap};
+use swc_ecma_loader::resolve::Resolve;
+
+use crate::cache::Cache;
+use crate::error::ModuleError;
+use crate::swc_utils::load_file_with_comments;
+
+use super::base::{module_package, ModulePackage};
+use super::node::{load_json_as_module, ModuleNode, VisitedModule};
+use super::pnp::{PnpResolver, EXTRACT_DIR};
+use super::resolver::{PackageResolver, REQUIRE_CONDITIONS};
+use super::tsconfig::TsConfig;
+
+/// Cache entry kind for module dependencies.
+const MODULES: &str = "modules";
+
+const PACKAGE: &str = "package.json";
+
+/// Resolved dependencies for a module stored in the analysis cache.
+#[derive(Serialize, Deserialize)]
+struct ModuleRecord {
+    resolved: Vec<(String, ResolvedFile)>,
+    optional: IndexSet<String>,
+    missing: Vec<String>,
+    /// Hash of the contents of the files consulted by the resolver.
+    inputs: BTreeMap<PathBuf, String>,
+}
+
+/// Resolved file name stored in the analysis cache.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+enum ResolvedFile {
+    Real { path: PathBuf },
+    Custom { name: String },
+}
+
+impl ModuleRecord {
+    fn new(file: &Path, node: &ModuleNode) -> Option<Self> {
+        // Do not cache modules with dependencies that failed to resolve
+        let dependencies = node.dependencies.as_ref().map(|d| d.len());
+        if dependencies.unwrap_or(0) != node.resolved.len() + node.missing.len()
+        {
+            return None;
+        }
+
+        let resolved = node
+            .resolved
+            .iter()
+            .map(|(spec, file_name)| {
+                let file = match file_name {
+                    FileName::Real(path) => {
+                        ResolvedFile::Real { path: path.clone() }
+                    }
+                    FileName::Custom(name) => {
+                        ResolvedFile::Custom { name: name.clone() }
+                    }
+                    _ => return None,
+                };
+                Some((spec.clone(), file))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let inputs = resolver_inputs(file, node)
+            .into_iter()
+            .filter_map(|path| {
+                let hash = file_hash(&path)?;
+                Some((path, hash))
+            })
+            .collect();
+        Some(Self {
+            resolved,
+            optional: node.optional.clone(),
+            missing: node.missing.clone(),
+            inputs,
+        })
+    }
+
+    /// Convert to a module node that has not been parsed, the record
+    /// is stale when a resolved file no longer exists or a file
+    /// consulted by the resolver has changed.
+    fn into_node(self, hash: Option<String>) -> Option<ModuleNode> {
+        for (path, input) in self.inputs.iter() {
+            if file_hash(path).as_ref() != Some(input) {
+                return None;
+            }
+        }
+
+        let resolved = self
+            .resolved
+            .into_iter()
+            .map(|(spec, file)| match file {
+                ResolvedFile::Real { path } => {
+                    if path.is_file() {
+                        Some((spec, FileName::Real(path)))
+                    } else {
+                        None
+                    }
+                }
+                ResolvedFile::Custom { name } => {
+                    Some((spec, FileName::Custom(name)))
+                }
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(ModuleNode {
+            id: 0,
+            module: None,
+            hash,
+            dependencies: None,
+            resolved,
+            suppressions: Default::default(),
+            dynamic_imports: Default::default(),
+            optional: self.optional,
+            missing: self.missing,
+        })
+    }
+}
+
+/// Files consulted by the resolver for the dependencies of a module.
+///
+/// These are the nearest `tsconfig.json` for the module (and the
+/// configurations it extends) and the
+/// nearest package.json and package manifest for the module and
+/// each resolved file.
+fn resolver_inputs(file: &Path, node: &ModuleNode) -> BTreeSet<PathBuf> {
+    let mut inputs = BTreeSet::new();
+    if let Some(dir) = file.parent() {
+        match TsConfig::find(dir) {
+            Ok(Some(tsconfig)) => inputs.extend(tsconfig.files().to_vec()),
+            _ => inputs.extend(TsConfig::find_file(dir)),
+        }
+    }
+    let resolved =
+        node.resolved
+            .iter()
+            .filter_map(|(_, file_name)| match file_name {
+                FileName::Real(path) => Some(path.as_path()),
+                _ => None,
+            });
+    for path in std::iter::once(file).chain(resolved) {
+        let nearest = path
+            .ancestors()
+            .skip(1)
+            .map(|dir| dir.join(PACKAGE))
+            .find(|file| file.is_file());
+        inputs.extend(nearest);
+        if let Some(package) = module_package(path) {
+            inputs.insert(package.base.join(PACKAGE));
+        }
+    }
+    inputs
+}
+
+/// Hash of the contents of a file.
+fn file_hash(path: &Path) -> Option<String> {
+    std::fs::read(path)
+        .ok()
+        .map(|contents| Cache::key(&[&contents]))
+}
+
+/// Resolver that shares a Plug'n'Play resolver with the graph
+/// so the graph can identify the package that owns a module.
+struct SharedPnpResolver(Arc<PnpResolver>);
+
+impl Resolve for SharedPnpResolver {
+    fn resolve(
+        &self,
+        base: &FileName,
+        specifier: &str,
+    ) -> Result<FileName, anyhow::Error> {
+        self.0.resolve(base, specifier)
+    }
+}
+
+/// Graph of the modules loaded from an entry point.
+pub struct ModuleGraph {
+    resolver: Box<dyn Resolve>,
+    pnp: Option<Arc<PnpResolver>>,
+    source_map: Arc<SourceMap>,
+    modules: DashMap<PathBuf, Arc<VisitedModule>>,
+    counter: AtomicU32,
+    cache: Option<Cache>,
+    conditions: Vec<String>,
+    manifest: Option<String>,
+    keep_going: bool,
+    errors: Mutex<Vec<ModuleError>>,
+}
+
+impl Default for ModuleGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ModuleGraph {
+    /// Create a module graph that resolves packages using
+    /// the conditions for `require()`.
+    pub fn new() -> Self {
+        Self::with_conditions(
+            REQUIRE_CONDITIONS.iter().map(|c| c.to_string()).collect(),
+        )
+    }
+
+    /// Create a module graph that resolves package `exports` and
+    /// `imports` using the given conditions.
+    pub fn with_conditions(conditions: Vec<String>) -> Self {
+        let mut graph = Self::with_resolver(Box::new(PackageResolver::new(
+            conditions.clone(),
+        )));
+        graph.conditions = conditions;
+        graph
+    }
+
+    /// Create a module graph that resolves packages using
+    /// a Yarn Plug'n'Play manifest.
+    ///
+    /// Zip archives are extracted to the `pnp` directory of the
+    /// analysis cache when given.
+    pub fn with_pnp<P: AsRef<Path>>(
+        manifest: P,
+        conditions: Vec<String>,
+        cache: Option<&Cache>,
+    ) -> Result<Self> {
+        let extract_dir = cache.map(|cache| cache.dir().join(EXTRACT_DIR));
+        let resolver = Arc::new(PnpResolver::new(
+            manifest.as_ref(),
+            conditions.clone(),
+            extract_dir,
+        )?);
+        let mut graph = Self::with_resolver(Box::new(SharedPnpResolver(
+            Arc::clone(&resolver),
+        )));
+        graph.pnp = Some(resolver);
+        graph.conditions = conditions;
+        // Resolved dependencies depend upon the package registry
+        graph.manifest = file_hash(manifest.as_ref());
+        Ok(graph)
+    }
+
+    /// Create a module graph using the given resolver.
+    pub fn with_resolver(resolver: Box<dyn Resolve>) -> Self {
+        Self {
+            resolver,
+            pnp: None,
+            source_map: Arc::new(Default::default()),
+            modules: DashMap::new(),
+            cache: None,
+            conditions: Vec::new(),
+            manifest: None,
+            counter: AtomicU32::new(0),
+            keep_going: false,
+            errors: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Collect parse and resolve errors rather than aborting.
+    pub fn keep_going(mut self, keep_going: bool) -> Self {
+        self.keep_going = keep_going;
+        self
+    }
+
+    /// Assign an analysis cache.
+    pub fn cache(mut self, cache: Option<Cache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// The analysis cache when assigned.
+    pub fn analysis_cache(&self) -> Option<&Cache> {
+        self.cache.as_ref()
+    }
+
+    /// The resolver for module specifiers.
+    pub fn resolver(&self) -> &Box<dyn Resolve> {
+        &self.resolver
+    }
+
+    /// Find the package that contains a module.
+    ///
+    /// When resolving with Plug'n'Play the package is identified by
+    /// the locator that owns the module otherwise by the nearest
+    /// package.json.
+    pub fn package(&self, path: &Path) -> Option<ModulePackage> {
+        self.pnp
+            .as_ref()
+            .and_then(|pnp| pnp.package(path))
+            .or_else(|| module_package(path))
+    }
+
+    /// The source map for the loaded modules.
+    pub fn source_map(&self) -> &Arc<SourceMap> {
+        &self.source_map
+    }
+
+    /// The loaded modules keyed by file path.
+    pub fn modules(&self) -> &DashMap<PathBuf, Arc<VisitedModule>> {
+        &self.modules
+    }
+
+    /// Get a loaded module.
+    pub fn get<P: AsRef<Path>>(&self, file: P) -> Option<Arc<VisitedModule>> {
+        self.modules
+            .get(file.as_ref())
+            .map(|entry| entry.value().clone())
+    }
+
+    /// Get the identifier for a loaded module.
+    pub fn id<P: AsRef<Path>>(&self, file: P) -> Option<u32> {
+        self.get(file).and_then(|module| match &*module {
+            VisitedModule::Module(_, node) | VisitedModule::Json(_, node) => {
+                Some(node.id)
+            }
+            VisitedModule::Builtin(_) => None,
+        })
+    }
+
+    /// The files for all the loaded modules.
+    pub fn files(&self) -> HashSet<PathBuf> {
+        self.modules
+            .iter()
+            .filter(|entry| entry.value().node().is_some())
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+
+    /// Remove the modules for files that have changed.
+    ///
+    /// Returns the number of modules that were removed.
+    pub fn invalidate<'a, I>(&self, files: I) -> usize
+    where
+        I: IntoIterator<Item = &'a PathBuf>,
+    {
+        files
+            .into_iter()
+            .filter(|file| self.modules.remove(*file).is_some())
+            .count()
+    }
+
+    /// Attempt to recover from an error.
+    ///
+    /// When keep going is enabled and the error is a module error it
+    /// is collected and `Ok` is returned, otherwise the error is returned.
+    pub fn recover(&self, error: anyhow::Error) -> Result<()> {
+        if self.keep_going {
+            if let Some(e) = error.downcast_ref::<ModuleError>() {
+                let mut errors = self.errors.lock().unwrap();
+                if !errors.contains(e) {
+                    errors.push(e.clone());
+                }
+                return Ok(());
+            }
+        }
+        Err(error)
+    }
+
+    /// Take the collected errors.
+    pub fn take_errors(&self) -> Vec<ModuleError> {
+        std::mem::take(&mut *self.errors.lock().unwrap())
+    }
+
+    /// Load an entry point and all of its dependencies into the graph.
+    ///
+    /// Modules are loaded breadth first; each newly discovered file is
+    /// parsed and resolved in parallel before the dependencies of the
+    /// loaded modules are discovered.
+    pub fn load<P: AsRef<Path>>(&self, file: P) -> Result<Arc<VisitedModule>> {
+        let entry = self.parse_file(file.as_ref())?;
+
+        let mut seen: HashSet<PathBuf> = HashSet::new();
+        seen.insert(file.as_ref().to_path_buf());
+        let mut pending = self.discover(&entry, &mut seen);
+
+        while !pending.is_empty() {
+            let loaded: Vec<Result<Arc<VisitedModule>>> = pending
+                .par_iter()
+                .map(|path| self.parse_file(path))
+                .collect();
+
+            pending = Vec::new();
+            for result in loaded {
+                match result {
+                    Ok(module) => {
+                        pending.append(&mut self.discover(&module, &mut seen))
+                    }
+                    Err(e) => self.recover(e)?,
+                }
+            }
+        }
+
+        Ok(entry)
+    }
+
+    /// Collect the resolved file paths for the dependencies of a
+    /// module that have not been seen.
+    fn discover(
+        &self,
+        module: &VisitedModule,
+        seen: &mut HashSet<PathBuf>,
+    ) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        if let Some(node) = module.node() {
+            for (_, file_name) in node.resolved.iter() {
+                if let FileName::Real(path) = file_name {
+                    if seen.insert(path.clone()) {
+                        paths.push(path.clone());
+                    }
+                }
+            }
+        }
+        paths
+    }
+
+    /// Parse a file, analyze dependencies and resolve dependency file paths.
+    pub fn parse_file<P: AsRef<Path>>(
+        &self,
+        file: P,
+    ) -> Result<Arc<VisitedModule>> {
+        let extension = file
+            .as_ref()
+            .extension()
+            .map(|s| s.to_string_lossy().to_string());
+        if let Some(ref extension) = extension {
+            let extension = &extension[..];
+            match extension {
+                "json" => self.parse_json(file),
+                _ => self.parse_module(file),
+            }
+        } else {
+            self.parse_module(file)
+        }
+    }
+
+    /// Parse a module using a cached value when available.
+    pub fn parse_module<P: AsRef<Path>>(
+        &self,
+        file: P,
+    ) -> Result<Arc<VisitedModule>> {
+        let buf = file.as_ref().to_path_buf();
+        if let Some(module) = self.get(&buf) {
+            return Ok(module);
+        }
+
+        let mut record_key = None;
+        let mut hash = None;
+        if let Some(cache) = &self.cache {
+            let contents = std::fs::read(file.as_ref())?;
+            let path = file.as_ref().to_string_lossy();
+            // Resolved dependencies depend upon the conditions
+            // and the Plug'n'Play manifest
+            let conditions = self.conditions.join(",");
+            let manifest = self.manifest.as_deref().unwrap_or_default();
+            let key = Cache::key(&[
+                path.as_bytes(),
+                conditions.as_bytes(),
+                manifest.as_bytes(),
+                &contents,
+            ]);
+            hash = Some(Cache::key(&[&contents]));
+
+            if let Some(mut node) = cache
+                .get::<ModuleRecord>(MODULES, &key)
+                .and_then(|record| record.into_node(hash.clone()))
+            {
+                node.id = self.counter.fetch_add(1, SeqCst);
+                let file_name = FileName::Real(buf.clone());
+                let module = Arc::new(VisitedModule::Module(file_name, node));
+                let entry = self.modules.entry(buf).or_insert(module);
+                return Ok(entry.value().clone());
+            }
+            record_key = Some(key);
+        }
+
+        let id = self.counter.fetch_add(1, SeqCst);
+        let (file_name, mut node) = self.parse_node(file.as_ref(), id)?;
+        node.hash = hash;
+        node.resolve(self, &file_name)?;
+
+        if let (Some(cache), Some(key)) = (&self.cache, record_key) {
+            if let Some(record) = ModuleRecord::new(file.as_ref(), &node) {
+                cache.put(MODULES, &key, &record)?;
+            }
+        }
+
+        let module = Arc::new(VisitedModule::Module(file_name, node));
+        let entry = self.modules.entry(buf).or_insert(module);
+        Ok(entry.value().clone())
+    }
+
+    /// Parse the module for a node that was loaded from the analysis cache.
+    ///
+    /// The returned node has the module AST and analysis but the
+    /// dependencies are not resolved. Returns `None` when the node
+    /// has already been parsed.
+    pub fn reparse(
+        &self,
+        file_name: &FileName,
+        node: &ModuleNode,
+    ) -> Result<Option<ModuleNode>> {
+        if node.module.is_some() {
+            return Ok(None);
+        }
+        match file_name {
+            FileName::Real(path) => {
+                let (_, mut parsed) = self.parse_node(path, node.id)?;
+                parsed.hash = node.hash.clone();
+                parsed.resolved = node.resolved.clone();
+                parsed.optional = node.optional.clone();
+                parsed.missing = node.missing.clone();
+                Ok(Some(parsed))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Parse and analyze a module.
+    fn parse_node(
+        &self,
+        file: &Path,
+        id: u32,
+    ) -> Result<(FileName, ModuleNode)> {
+        let comments: SingleThreadedComments = Default::default();
+        let (file_name, source_map, module) = load_file_with_comments(
+            file,
+            Some(Arc::clone(&self.source_map)),
+            Some(&comments),
+        )?;
+
+        let mut node = ModuleNode {
+            module: Some(Arc::new(module)),
+            hash: None,
+            dependencies: None,
+            resolved: Default::default(),
+            suppressions: Default::default(),
+            dynamic_imports: Default::default(),
+            optional: Default::default(),
+            missing: Default::default(),
+            id,
+        };
+        node.analyze(&comments, &source_map);
+        Ok((file_name, node))
+    }
+
+    /// Parse a JSON file as a module.
+    pub fn parse_json<P: AsRef<Path>>(
+        &self,
+        file: P,
+    ) -> Result<Arc<VisitedModule>> {
+        let buf = file.as_ref().to_path_buf();
+        if let Some(module) = self.get(&buf) {
+            return Ok(module);
+        }
+
+        let id = self.counter.fetch_add(1, SeqCst);
+        let source_file = self.source_map.load_file(file.as_ref())?;
+        let file_name = FileName::Real(file.as_ref().to_path_buf());
+        let module =
+            load_json_as_module(&file_name, &self.source_map, &source_file)?;
+
+        let node = ModuleNode {
+            id,
+            module: Some(Arc::new(module)),
+            hash: None,
+            dependencies: None,
+            resolved: vec![],
+            suppressions: Default::default(),
+            dynamic_imports: Default::default(),
+            optional: Default::default(),
+            missing: Default::default(),
+        };
+
+        let module = Arc::new(VisitedModule::Json(file_name, node));
+        let entry = self.modules.entry(buf).or_insert(module);
+        Ok(entry.value().clone())
+    }
+}
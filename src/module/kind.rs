@@ -0,0 +1,216 @@
+//! Determine whether a module is an ES module or a CommonJS module.
+//!
+//! Detection follows the rules used by Node:
+//!
+//! 1. Files with a `.mjs` or `.mts` extension are ES modules and files
+//!    with a `.cjs` or `.cts` extension are CommonJS modules.
+//! 2. Otherwise the `type` field of the nearest package.json decides.
+//! 3. When there is no `type` field the syntax decides; a module that
+//!    uses `import` or `export` declarations or `import.meta` is an ES
+//!    module otherwise it is a CommonJS module.
+//!
+//! Modules that use ES module syntax and also reference `exports`,
+//! `module.exports` or call `require()` without an explicit type are
+//! mixed.
+//!
+//! A JavaScript module declared as CommonJS that uses ES module syntax
+//! is an error. TypeScript modules declared as CommonJS are compiled
+//! to CommonJS by `tsc` so ES module syntax in a TypeScript module is
+//! treated as a mixed module that interoperates with CommonJS.
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+use swc_ecma_ast::*;
+use swc_ecma_visit::{Node, Visit, VisitWith};
+
+use crate::helpers::{EXPORTS, MODULE, REQUIRE};
+use crate::swc_utils::TYPESCRIPT_EXTENSIONS;
+
+const PACKAGE: &str = "package.json";
+const TYPE_MODULE: &str = "module";
+const TYPE_COMMONJS: &str = "commonjs";
+
+/// Kind of module.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub enum ModuleKind {
+    /// Module uses ES module and CommonJS syntax.
+    #[serde(rename = "mixed")]
+    Mixed,
+    /// ES module.
+    #[serde(rename = "esm")]
+    Esm,
+    /// CommonJS module.
+    #[serde(rename = "js")]
+    Cjs,
+}
+
+#[derive(Deserialize)]
+struct PackageJson {
+    r#type: Option<String>,
+}
+
+/// Module syntax detected in a module.
+#[derive(Debug, Default)]
+pub struct ModuleSyntax {
+    /// Module uses `import` or `export` declarations or `import.meta`.
+    pub esm: bool,
+    /// Module references `exports` or `module.exports`.
+    pub cjs: bool,
+    /// Module calls `require()`.
+    pub require: bool,
+}
+
+impl ModuleSyntax {
+    /// Detect the module syntax used by a module.
+    pub fn detect(module: &Module) -> Self {
+        let mut syntax: ModuleSyntax = Default::default();
+        module.visit_children_with(&mut syntax);
+        syntax
+    }
+
+    /// Determine if the module does not use any module syntax.
+    pub fn is_script(&self) -> bool {
+        !self.esm && !self.cjs && !self.require
+    }
+}
+
+impl Visit for ModuleSyntax {
+    fn visit_module_decl(&mut self, n: &ModuleDecl, _: &dyn Node) {
+        match n {
+            ModuleDecl::Import(_)
+            | ModuleDecl::ExportDecl(_)
+            | ModuleDecl::ExportNamed(_)
+            | ModuleDecl::ExportDefaultDecl(_)
+            | ModuleDecl::ExportDefaultExpr(_)
+            | ModuleDecl::ExportAll(_) => {
+                self.esm = true;
+            }
+            _ => {}
+        }
+        n.visit_children_with(self);
+    }
+
+    fn visit_meta_prop_expr(&mut self, n: &MetaPropExpr, _: &dyn Node) {
+        if n.meta.sym.as_ref() == "import" {
+            self.esm = true;
+        }
+    }
+
+    fn visit_expr(&mut self, n: &Expr, _: &dyn Node) {
+        if let Expr::Ident(ident) = n {
+            if ident.sym.as_ref() == EXPORTS {
+                self.cjs = true;
+            }
+        }
+        n.visit_children_with(self);
+    }
+
+    fn visit_member_expr(&mut self, n: &MemberExpr, _: &dyn Node) {
+        if let (ExprOrSuper::Expr(obj), Expr::Ident(prop), false) =
+            (&n.obj, &*n.prop, n.computed)
+        {
+            if let Expr::Ident(obj) = &**obj {
+                if obj.sym.as_ref() == MODULE && prop.sym.as_ref() == EXPORTS {
+                    self.cjs = true;
+                }
+            }
+        }
+
+        // Property names are not references to `exports`
+        if let ExprOrSuper::Expr(obj) = &n.obj {
+            self.visit_expr(obj, n);
+        }
+        if n.computed {
+            self.visit_expr(&n.prop, n);
+        }
+    }
+
+    fn visit_call_expr(&mut self, n: &CallExpr, _: &dyn Node) {
+        if let ExprOrSuper::Expr(callee) = &n.callee {
+            if let Expr::Ident(ident) = &**callee {
+                if ident.sym.as_ref() == REQUIRE {
+                    self.require = true;
+                }
+            }
+        }
+        n.visit_children_with(self);
+    }
+
+    fn visit_assign_expr(&mut self, n: &AssignExpr, _: &dyn Node) {
+        if let PatOrExpr::Pat(pat) = &n.left {
+            if let Pat::Ident(ident) = &**pat {
+                if ident.id.sym.as_ref() == EXPORTS {
+                    self.cjs = true;
+                }
+            }
+        }
+        n.visit_children_with(self);
+    }
+}
+
+/// Determine the kind of a module.
+///
+/// The file is used to check the extension and find the package.json
+/// for the module, when no file is available only the syntax is used.
+pub fn module_kind(file: Option<&Path>, module: &Module) -> Result<ModuleKind> {
+    let syntax = ModuleSyntax::detect(module);
+    let declared = file.and_then(declared_kind);
+    match declared {
+        Some(ModuleKind::Cjs) if syntax.esm && is_typescript(file) => {
+            Ok(ModuleKind::Mixed)
+        }
+        Some(ModuleKind::Cjs) if syntax.esm => bail!(
+            "module {} is CommonJS but uses ES module syntax",
+            file.unwrap().display()
+        ),
+        Some(kind) => Ok(kind),
+        None if syntax.esm && (syntax.cjs || syntax.require) => {
+            Ok(ModuleKind::Mixed)
+        }
+        None if syntax.esm => Ok(ModuleKind::Esm),
+        None => Ok(ModuleKind::Cjs),
+    }
+}
+
+/// Get the kind declared by the file extension or the
+/// `type` field of the nearest package.json.
+pub(crate) fn declared_kind(file: &Path) -> Option<ModuleKind> {
+    match file.extension().and_then(|e| e.to_str()) {
+        Some("mjs") | Some("mts") => Some(ModuleKind::Esm),
+        Some("cjs") | Some("cts") => Some(ModuleKind::Cjs),
+        Some("js") | Some("jsx") | Some("ts") | Some("tsx") | None => {
+            package_type(file)
+        }
+        _ => None,
+    }
+}
+
+/// Determine if a file has a TypeScript extension.
+fn is_typescript(file: Option<&Path>) -> bool {
+    file.and_then(|file| file.extension())
+        .and_then(|e| e.to_str())
+        .map(|e| TYPESCRIPT_EXTENSIONS.contains(&e))
+        .unwrap_or(false)
+}
+
+/// Find the `type` field in the nearest package.json.
+fn package_type(file: &Path) -> Option<ModuleKind> {
+    let mut parent = file.parent();
+    while let Some(dir) = parent {
+        let pkg = dir.join(PACKAGE);
+        if pkg.is_file() {
+            let json = std::fs::read_to_string(&pkg)
+                .ok()
+                .and_then(|s| serde_json::from_str::<PackageJson>(&s).ok())?;
+            return match json.r#type.as_deref() {
+                Some(TYPE_MODULE) => Some(ModuleKind::Esm),
+                Some(TYPE_COMMONJS) => Some(ModuleKind::Cjs),
+                _ => None,
+            };
+        }
+        parent = dir.parent();
+    }
+    None
+}
@@ -2,5 +2,11 @@
 
 pub mod base;
 pub mod dependencies;
+pub mod graph;
+pub mod kind;
 pub mod node;
+pub mod optional;
 pub mod parser;
+pub mod pnp;
+pub mod resolver;
+pub mod tsconfig;
@@ -4,39 +4,30 @@
 //! iterator for resolving and loading dependencies.
 //!
 
-use std::path::{Path, PathBuf};
-use std::sync::{
-    atomic::{AtomicU32, Ordering::SeqCst},
-    Arc,
-};
+use std::collections::HashSet;
+use std::sync::Arc;
 
-use anyhow::{anyhow, Context, Result};
-use dashmap::DashMap;
-use std::lazy::SyncLazy;
+use anyhow::Result;
+use indexmap::IndexSet;
 
 use swc_common::{
     comments::SingleThreadedComments, FileName, SourceFile, SourceMap,
 };
-use swc_ecma_ast::{Module, TargetEnv};
+use swc_ecma_ast::Module;
 use swc_ecma_dep_graph::{analyze_dependencies, DependencyDescriptor};
 
-use swc_ecma_loader::{resolve::Resolve, resolvers::node::NodeModulesResolver};
-
-use crate::module::dependencies::is_builtin_module;
-use crate::swc_utils::load_file;
-
-/// Counter of module ids.
-static COUNTER: SyncLazy<AtomicU32> = SyncLazy::new(|| AtomicU32::new(0));
-
-/// Cache of visited modules.
-static CACHE: SyncLazy<DashMap<PathBuf, Arc<VisitedModule>>> =
-    SyncLazy::new(|| DashMap::new());
+use swc_ecma_loader::resolve::Resolve;
+use swc_ecma_visit::VisitWith;
 
-/// Get the map of cached modules.
-pub fn cached_modules(
-) -> &'static SyncLazy<DashMap<PathBuf, Arc<VisitedModule>>> {
-    &CACHE
-}
+use crate::error::ModuleError;
+use crate::module::{
+    dependencies::is_builtin_module, graph::ModuleGraph,
+    optional::OptionalAnalysis,
+};
+use crate::policy::analysis::{
+    dynamic_import::{DynamicImport, DynamicImportAnalysis},
+    suppression::Suppressions,
+};
 
 /// Stores the data for a visited module dependency.
 pub enum VisitedModule {
@@ -48,6 +39,27 @@ pub enum VisitedModule {
     Builtin(FileName),
 }
 
+impl VisitedModule {
+    /// The file name for the module.
+    pub fn file_name(&self) -> &FileName {
+        match self {
+            VisitedModule::Module(file_name, _)
+            | VisitedModule::Json(file_name, _)
+            | VisitedModule::Builtin(file_name) => file_name,
+        }
+    }
+
+    /// The parsed module node; builtin modules do not have a node.
+    pub fn node(&self) -> Option<&ModuleNode> {
+        match self {
+            VisitedModule::Module(_, node) | VisitedModule::Json(_, node) => {
+                Some(node)
+            }
+            VisitedModule::Builtin(_) => None,
+        }
+    }
+}
+
 /// Represents a visited dependency.
 #[derive(Debug)]
 pub struct VisitedDependency<'a> {
@@ -57,6 +69,8 @@ pub struct VisitedDependency<'a> {
     pub file_name: FileName,
     /// Whether this dependency is the last child.
     pub last: bool,
+    /// Whether this dependency is optional.
+    pub optional: bool,
     /// A parsed module for the dependency.
     pub node: &'a Option<&'a ModuleNode>,
     /// The current visitor state.
@@ -69,7 +83,7 @@ pub struct VisitedDependency<'a> {
 #[derive(Debug)]
 pub struct BranchState {
     /// Determine if this node is the last child
-    /// of it's parent.
+    /// of its parent.
     pub last: bool,
 }
 
@@ -84,136 +98,111 @@ pub struct VisitState {
     pub parents: Vec<FileName>,
 }
 
-/// Parse a module using a cached value when available.
-pub fn parse_module<P: AsRef<Path>>(
-    file: P,
-    resolver: &Box<dyn Resolve>,
-    source_map: Arc<SourceMap>,
-) -> Result<Arc<VisitedModule>> {
-    let buf = file.as_ref().to_path_buf();
-    if let Some(entry) = CACHE.get(&buf) {
-        let module = entry.value();
-        return Ok(module.clone());
-    }
-
-    let (file_name, _source_map, module) =
-        load_file(file.as_ref(), Some(source_map))?;
-
-    let id = COUNTER.fetch_add(1, SeqCst);
-    let comments: SingleThreadedComments = Default::default();
-    let mut node = ModuleNode {
-        module: Arc::new(module),
-        dependencies: None,
-        resolved: Default::default(),
-        id,
-    };
-    node.analyze(&comments);
-    node.resolve(resolver, &file_name)?;
-
-    // Don't bother walking dependencies that have already
-    // been visited.
-    //
-    // Note that without this performance is terrible as lots
-    // of iterations will be performed whilst visiting the dependency
-    // graph.
-    node.resolved = node
-        .resolved
-        .into_iter()
-        .filter(|(_, file_name)| {
-            if let FileName::Real(module_path) = &file_name {
-                return CACHE.get(module_path).is_none();
-            }
-            true
-        })
-        .collect();
-
-    let module = Arc::new(VisitedModule::Module(file_name, node));
-    let entry = CACHE.entry(buf).or_insert(module);
-    Ok(entry.value().clone())
-}
-
-/// Parse a JSON file as a module.
-pub fn parse_json<P: AsRef<Path>>(
-    file: P,
-    _resolver: &Box<dyn Resolve>,
-    source_map: Arc<SourceMap>,
-) -> Result<Arc<VisitedModule>> {
-    let buf = file.as_ref().to_path_buf();
-    if let Some(entry) = CACHE.get(&buf) {
-        let module = entry.value();
-        return Ok(module.clone());
-    }
-
-    let id = COUNTER.fetch_add(1, SeqCst);
-    let source_file = source_map.load_file(file.as_ref())?;
-    let file_name = FileName::Real(file.as_ref().to_path_buf());
-    let module = load_json_as_module(&source_file)?;
-
-    let node = ModuleNode {
-        id,
-        module: Arc::new(module),
-        dependencies: None,
-        resolved: vec![],
-    };
-
-    let module = Arc::new(VisitedModule::Json(file_name, node));
-    let entry = CACHE.entry(buf).or_insert(module);
-    Ok(entry.value().clone())
-}
-
-/// Parse a file, analyze dependencies and resolve dependency file paths.
-pub fn parse_file<P: AsRef<Path>>(
-    file: P,
-    resolver: &Box<dyn Resolve>,
-    source_map: Arc<SourceMap>,
-) -> Result<Arc<VisitedModule>> {
-    let extension = file
-        .as_ref()
-        .extension()
-        .map(|s| s.to_string_lossy().to_string());
-    if let Some(ref extension) = extension {
-        let extension = &extension[..];
-        match extension {
-            "json" => parse_json(file, resolver, source_map),
-            _ => parse_module(file, resolver, source_map),
-        }
-    } else {
-        parse_module(file, resolver, source_map)
-    }
-}
-
-/// Encapsulates a module and it's dependencies.
+/// Encapsulates a module and its dependencies.
 #[derive(Debug)]
 pub struct ModuleNode {
     /// The module identifier.
+    ///
+    /// Identifiers are assigned in load order and are only unique
+    /// within a graph, bundles use identifiers derived from the path.
     pub id: u32,
     /// The underlying module AST node.
-    pub module: Arc<Module>,
+    ///
+    /// Modules loaded from the analysis cache are not parsed.
+    pub module: Option<Arc<Module>>,
+    /// Hash of the module contents when using the analysis cache.
+    pub hash: Option<String>,
     /// The parsed dependencies of this module.
     pub dependencies: Option<Vec<DependencyDescriptor>>,
     /// The resolved paths for the dependencies.
     pub resolved: Vec<(String, FileName)>,
+    /// Inline suppression directives declared in comments.
+    pub suppressions: Suppressions,
+    /// Calls to `require()` or `import()` that cannot be resolved statically.
+    pub dynamic_imports: Vec<DynamicImport>,
+    /// Specifiers for dependencies that are optional at every call site.
+    pub optional: IndexSet<String>,
+    /// Optional dependencies that could not be resolved.
+    pub missing: Vec<String>,
 }
 
 impl ModuleNode {
-    /// Analyze the dependencies for this module.
-    pub fn analyze(&mut self, comments: &SingleThreadedComments) {
-        let deps = analyze_dependencies(&self.module, comments);
+    /// Analyze the dependencies, dynamic imports and suppression
+    /// directives for this module.
+    pub fn analyze(
+        &mut self,
+        comments: &SingleThreadedComments,
+        source_map: &SourceMap,
+    ) {
+        let module = match &self.module {
+            Some(module) => Arc::clone(module),
+            None => return,
+        };
+
+        let deps = analyze_dependencies(&module, comments);
+
+        let mut optional: OptionalAnalysis = Default::default();
+        module.visit_children_with(&mut optional);
+        self.optional = optional.specifiers(&deps);
+
         self.dependencies = if deps.is_empty() { None } else { Some(deps) };
+        self.suppressions = Suppressions::parse(comments, source_map);
+
+        let mut dynamic_imports = DynamicImportAnalysis::new(source_map);
+        module.visit_children_with(&mut dynamic_imports);
+        self.dynamic_imports = dynamic_imports.imports;
+    }
+
+    /// Determine if a dependency specifier is optional.
+    pub fn is_optional(&self, spec: &str) -> bool {
+        self.optional.contains(spec)
+    }
+
+    /// Determine if a dependency specifier is optional and could
+    /// not be resolved.
+    pub fn is_missing(&self, spec: &str) -> bool {
+        self.missing.iter().any(|s| s == spec)
     }
 
     /// Resolve the dependencies for this module.
+    ///
+    /// Optional dependencies that cannot be resolved are skipped
+    /// and when keep going is enabled dependencies that cannot be
+    /// resolved are collected as errors by the graph.
     pub fn resolve(
         &mut self,
-        resolver: &Box<dyn Resolve>,
+        graph: &ModuleGraph,
         base: &FileName,
     ) -> Result<()> {
         if let Some(deps) = &self.dependencies {
             for dep in deps {
                 let spec = format!("{}", dep.specifier);
-                let file_name = resolver.resolve(base, &spec).context(
-                    format!("Failed to resolve module for {}", &spec),
-                )?;
+                let file_name = match graph.resolver().resolve(base, &spec) {
+                    Ok(file_name) => file_name,
+                    Err(e) => {
+                        if self.optional.contains(&spec) {
+                            log::warn!(
+                                "Skipping optional dependency {} in {} ({})",
+                                &spec,
+                                base,
+                                e
+                            );
+                            if !self.is_missing(&spec) {
+                                self.missing.push(spec);
+                            }
+                            continue;
+                        }
+                        let error = ModuleError::resolve(
+                            base,
+                            graph.source_map(),
+                            dep.span,
+                            spec,
+                            e.to_string(),
+                        );
+                        graph.recover(error.into())?;
+                        continue;
+                    }
+                };
                 self.resolved.push((spec, file_name));
             }
         }
@@ -222,24 +211,19 @@ impl ModuleNode {
 
     /// Iterate the resolved dependencies of this module and
     /// attempt to load a module for each resolved dependency.
-    fn iter<'a>(&'a self, source_map: Arc<SourceMap>) -> NodeIterator<'a> {
+    fn iter<'a>(&'a self, graph: &'a ModuleGraph) -> NodeIterator<'a> {
         NodeIterator {
             node: self,
             index: 0,
-            resolver: Box::new(NodeModulesResolver::new(
-                TargetEnv::Node,
-                Default::default(),
-            )),
-            source_map,
+            graph,
         }
     }
 
     /// Visit all dependencies of this node recursively.
-    pub fn visit<F>(
-        &self,
-        source_map: Arc<SourceMap>,
-        callback: &mut F,
-    ) -> Result<()>
+    ///
+    /// Every path through the graph is followed so a module is visited
+    /// each time it is reached; use `walk()` to visit each module once.
+    pub fn visit<F>(&self, graph: &ModuleGraph, callback: &mut F) -> Result<()>
     where
         F: FnMut(VisitedDependency) -> Result<()>,
     {
@@ -247,14 +231,14 @@ impl ModuleNode {
             open: Vec::new(),
             parents: Vec::new(),
         };
-        self.visit_all(self, &mut state, source_map, callback)
+        self.visit_all(self, &mut state, graph, callback)
     }
 
     fn visit_all<F>(
         &self,
         node: &ModuleNode,
         state: &mut VisitState,
-        source_map: Arc<SourceMap>,
+        graph: &ModuleGraph,
         callback: &mut F,
     ) -> Result<()>
     where
@@ -262,25 +246,25 @@ impl ModuleNode {
     {
         state.open.push(BranchState { last: false });
 
-        for res in node.iter(Arc::clone(&source_map)) {
-            let (i, spec, parsed) = res?;
+        for res in node.iter(graph) {
+            let (i, spec, parsed) = match res {
+                Ok(res) => res,
+                Err(e) => {
+                    graph.recover(e)?;
+                    continue;
+                }
+            };
             let last = i == (node.resolved.len() - 1);
             state.open.last_mut().unwrap().last = last;
 
-            let (file_name, dep) = match &*parsed {
-                VisitedModule::Module(file_name, dep) => (file_name, Some(dep)),
-                VisitedModule::Json(file_name, dep) => (file_name, Some(dep)),
-                VisitedModule::Builtin(file_name) => (file_name, None),
-            };
-
-            //println!("Visiting {:#?}", file_name);
-
+            let (file_name, dep) = (parsed.file_name(), parsed.node());
             let cycles = state.parents.iter().find(|p| p == &file_name);
 
             let dependency = VisitedDependency {
+                optional: node.is_optional(&spec),
                 spec,
                 file_name: file_name.clone(),
-                last: i == node.resolved.len() - 1,
+                last,
                 node: &dep,
                 state,
                 cycles,
@@ -295,12 +279,83 @@ impl ModuleNode {
             if let Some(dep) = dep {
                 if !dep.resolved.is_empty() {
                     state.parents.push(file_name.clone());
-                    self.visit_all(
-                        &dep,
-                        state,
-                        Arc::clone(&source_map),
-                        callback,
-                    )?;
+                    self.visit_all(&dep, state, graph, callback)?;
+                    state.parents.pop();
+                }
+            }
+        }
+
+        state.open.pop();
+
+        Ok(())
+    }
+
+    /// Walk the graph from this node visiting each module once.
+    ///
+    /// The dependencies of a module are only walked the first time the
+    /// module is reached so shared sub-graphs are not visited again.
+    /// Builtin modules do not have dependencies and are passed to the
+    /// callback each time they are reached.
+    pub fn walk<F>(&self, graph: &ModuleGraph, callback: &mut F) -> Result<()>
+    where
+        F: FnMut(VisitedDependency) -> Result<()>,
+    {
+        let mut state = VisitState {
+            open: Vec::new(),
+            parents: Vec::new(),
+        };
+        let mut visited = HashSet::new();
+        visited.insert(self.id);
+        self.walk_all(self, &mut state, &mut visited, graph, callback)
+    }
+
+    fn walk_all<F>(
+        &self,
+        node: &ModuleNode,
+        state: &mut VisitState,
+        visited: &mut HashSet<u32>,
+        graph: &ModuleGraph,
+        callback: &mut F,
+    ) -> Result<()>
+    where
+        F: FnMut(VisitedDependency) -> Result<()>,
+    {
+        state.open.push(BranchState { last: false });
+
+        for res in node.iter(graph) {
+            let (i, spec, parsed) = match res {
+                Ok(res) => res,
+                Err(e) => {
+                    graph.recover(e)?;
+                    continue;
+                }
+            };
+            let last = i == (node.resolved.len() - 1);
+            state.open.last_mut().unwrap().last = last;
+
+            let (file_name, dep) = (parsed.file_name(), parsed.node());
+            if let Some(dep) = dep {
+                if !visited.insert(dep.id) {
+                    continue;
+                }
+            }
+
+            let dependency = VisitedDependency {
+                optional: node.is_optional(&spec),
+                spec,
+                file_name: file_name.clone(),
+                last,
+                node: &dep,
+                state,
+                cycles: None,
+            };
+
+            callback.call_mut((dependency,))?;
+
+            if let Some(dep) = dep {
+                if !dep.resolved.is_empty() {
+                    state.parents.push(file_name.clone());
+                    self.walk_all(&dep, state, visited, graph, callback)?;
                     state.parents.pop();
                 }
             }
@@ -315,9 +370,8 @@ impl ModuleNode {
 /// Iterate the resolved dependencies of a module node.
 pub struct NodeIterator<'a> {
     node: &'a ModuleNode,
-    resolver: Box<dyn Resolve>,
+    graph: &'a ModuleGraph,
     index: usize,
-    source_map: Arc<SourceMap>,
 }
 
 impl<'a> Iterator for NodeIterator<'a> {
@@ -332,17 +386,13 @@ impl<'a> Iterator for NodeIterator<'a> {
 
             match &resolved.1 {
                 FileName::Real(file_name) => {
-                    return match parse_file(
-                        file_name,
-                        &self.resolver,
-                        Arc::clone(&self.source_map),
-                    ) {
+                    return match self.graph.parse_file(file_name) {
                         Ok(parsed) => Some(Ok((
                             self.index - 1,
                             resolved.0.clone(),
                             parsed,
                         ))),
-                        Err(e) => Some(Err(anyhow!(e))),
+                        Err(e) => Some(Err(e)),
                     };
                 }
                 FileName::Custom(file_name) => {
@@ -365,8 +415,12 @@ impl<'a> Iterator for NodeIterator<'a> {
 }
 
 /// Load a JSON file and convert to a CJS module.
-fn load_json_as_module(fm: &Arc<SourceFile>) -> Result<Module> {
-    use swc_common::{input::SourceFileInput, DUMMY_SP};
+pub(super) fn load_json_as_module(
+    file_name: &FileName,
+    source_map: &SourceMap,
+    fm: &Arc<SourceFile>,
+) -> Result<Module> {
+    use swc_common::{input::SourceFileInput, Spanned, DUMMY_SP};
     use swc_ecma_ast::{EsVersion, *};
     use swc_ecma_parser::{lexer::Lexer, Parser, Syntax};
 
@@ -378,7 +432,12 @@ fn load_json_as_module(fm: &Arc<SourceFile>) -> Result<Module> {
     );
     let mut parser = Parser::new_from(lexer);
     let expr = parser.parse_expr().map_err(|err| {
-        anyhow!("failed parse json as javascript object: {:#?}", err)
+        ModuleError::parse(
+            file_name,
+            source_map,
+            err.span(),
+            format!("failed to parse json: {}", err.kind().msg()),
+        )
     })?;
 
     let export = ModuleItem::Stmt(Stmt::Expr(ExprStmt {
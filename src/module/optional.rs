@@ -0,0 +1,111 @@
+//! Detect optional dependencies.
+//!
+//! A dependency is optional when the call to `require()` or `import()`
+//! is guarded by a `try` block:
+//!
+//! ```javascript
+//! try { require('optional-native') } catch {}
+//! ```
+//!
+//! Or when the specifier is checked using the `require.resolve()` idiom
+//! inside a `try` block:
+//!
+//! ```javascript
+//! let hasNative = false;
+//! try { require.resolve('optional-native'); hasNative = true; } catch {}
+//! if (hasNative) require('optional-native');
+//! ```
+//!
+//! Functions declared inside a `try` block are not guarded as they
+//! may be called from outside of the block.
+//!
+//! Guarded calls are recorded per call site so a dependency is only
+//! optional when every call site for the specifier is guarded.
+use indexmap::IndexSet;
+
+use swc_common::Span;
+use swc_ecma_ast::*;
+use swc_ecma_dep_graph::DependencyDescriptor;
+use swc_ecma_visit::{Node, Visit, VisitWith};
+
+use crate::policy::analysis::dynamic_import::{
+    is_import_expr, is_require_expr, is_require_resolve_expr,
+};
+
+/// Visit a module and collect the call sites for optional dependencies.
+#[derive(Default)]
+pub struct OptionalAnalysis {
+    guarded: usize,
+    /// Spans for calls to `require()` or `import()` in a `try` block.
+    pub call_sites: IndexSet<Span>,
+    /// Specifiers checked using `require.resolve()` in a `try` block.
+    pub resolved: IndexSet<String>,
+}
+
+impl OptionalAnalysis {
+    /// Get the specifiers that are optional at every call site.
+    pub fn specifiers(
+        &self,
+        dependencies: &[DependencyDescriptor],
+    ) -> IndexSet<String> {
+        let is_optional = |dep: &DependencyDescriptor| {
+            self.call_sites.contains(&dep.span)
+                || self.resolved.contains(dep.specifier.as_ref())
+        };
+        dependencies
+            .iter()
+            .filter(|dep| {
+                dependencies
+                    .iter()
+                    .filter(|other| other.specifier == dep.specifier)
+                    .all(is_optional)
+            })
+            .map(|dep| dep.specifier.as_ref().to_string())
+            .collect()
+    }
+
+    fn visit_unguarded<T: VisitWith<Self>>(&mut self, n: &T) {
+        let guarded = std::mem::take(&mut self.guarded);
+        n.visit_children_with(self);
+        self.guarded = guarded;
+    }
+}
+
+impl Visit for OptionalAnalysis {
+    fn visit_try_stmt(&mut self, n: &TryStmt, _: &dyn Node) {
+        self.guarded += 1;
+        n.block.visit_children_with(self);
+        self.guarded -= 1;
+
+        if let Some(handler) = &n.handler {
+            handler.visit_children_with(self);
+        }
+        if let Some(finalizer) = &n.finalizer {
+            finalizer.visit_children_with(self);
+        }
+    }
+
+    fn visit_function(&mut self, n: &Function, _: &dyn Node) {
+        self.visit_unguarded(n);
+    }
+
+    fn visit_arrow_expr(&mut self, n: &ArrowExpr, _: &dyn Node) {
+        self.visit_unguarded(n);
+    }
+
+    fn visit_expr(&mut self, n: &Expr, _: &dyn Node) {
+        if self.guarded > 0 {
+            if let Expr::Call(call) = n {
+                if is_require_expr(n).or_else(|| is_import_expr(n)).is_some() {
+                    self.call_sites.insert(call.span);
+                }
+            }
+
+            if let Some(spec) = is_require_resolve_expr(n) {
+                self.resolved.insert(spec.as_ref().to_string());
+            }
+        }
+
+        n.visit_children_with(self);
+    }
+}
@@ -1,21 +1,19 @@
 //! Helper to parse all modules in a dependency graph for performance timing purposes.
 use std::path::Path;
-use std::sync::Arc;
 
 use anyhow::Result;
 
-use swc_common::SourceMap;
-use swc_ecma_loader::{resolve::Resolve, resolvers::node::NodeModulesResolver};
-
-use crate::module::node::{
-    cached_modules, parse_file, VisitedDependency, VisitedModule,
+use crate::module::{
+    graph::ModuleGraph,
+    node::{VisitedDependency, VisitedModule},
 };
 
 /// Parse all the modules in a dependency graph.
-pub fn parse<P: AsRef<Path>>(file: P) -> Result<(usize, usize)> {
-    let resolver: Box<dyn Resolve> = Box::new(NodeModulesResolver::default());
-    let source_map: Arc<SourceMap> = Arc::new(Default::default());
-    let module = parse_file(file.as_ref(), &resolver, Arc::clone(&source_map))?;
+pub fn parse<P: AsRef<Path>>(
+    graph: &ModuleGraph,
+    file: P,
+) -> Result<(usize, usize)> {
+    let module = graph.load(file.as_ref())?;
 
     let node = match &*module {
         VisitedModule::Module(_, node) => Some(node),
@@ -32,11 +30,8 @@ pub fn parse<P: AsRef<Path>>(file: P) -> Result<(usize, usize)> {
     };
 
     if let Some(node) = node {
-        node.visit(source_map, &mut visitor)?;
+        node.walk(graph, &mut visitor)?;
     }
 
-    // WTF: Visited 29146348 modules!
-    //eprintln!("Visited {} modules!", visited_count);
-
-    Ok((cached_modules().len(), visited_count))
+    Ok((graph.modules().len(), visited_count))
 }
@@ -0,0 +1,536 @@
+//! Resolver for projects installed using Yarn Plug'n'Play.
+//!
+//! The package registry is read from the `.pnp.data.json` manifest or
+//! from the runtime state inlined in the `.pnp.cjs` loader. A bare
+//! specifier is resolved by finding the package that owns the importing
+//! module and looking up the dependency in the package registry.
+//!
+//! Packages stored in the zip cache are extracted so that modules can
+//! be parsed from disk; archives are extracted to the `pnp` directory
+//! of the analysis cache when one is assigned otherwise to a shared
+//! directory in the system temporary directory.
+//!
+//! Packages are identified by the locator that owns a module (see
+//! `PnpResolver::package()`) so aliased dependencies are named by the
+//! package they refer to. Virtual packages share the files of the
+//! package they were derived from; a virtual locator is preferred as
+//! the owner of the files as it declares the resolved peer dependencies.
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering::SeqCst},
+    Mutex,
+};
+
+use anyhow::{bail, Context, Error, Result};
+use serde::Deserialize;
+
+use swc_common::FileName;
+use swc_ecma_ast::*;
+use swc_ecma_visit::{Node, Visit, VisitWith};
+
+use swc_ecma_loader::resolve::Resolve;
+
+use super::{
+    base::{module_package, ModulePackage},
+    dependencies::is_builtin_module,
+    resolver::{is_bare_specifier, split_specifier, PackageResolver},
+};
+use crate::swc_utils::load_file;
+
+/// Manifest file names in order of preference.
+const MANIFESTS: &[&str] = &[".pnp.data.json", ".pnp.cjs", ".pnp.js"];
+
+/// Directory in the analysis cache where zip archives are extracted.
+pub const EXTRACT_DIR: &str = "pnp";
+
+/// Directory in the system temporary directory where zip archives
+/// are extracted when there is no analysis cache.
+const TEMP_EXTRACT_DIR: &str = "basalt-pnp";
+
+/// Counter for the temporary directories used whilst extracting.
+static EXTRACTING: AtomicUsize = AtomicUsize::new(0);
+
+const PACKAGE: &str = "package.json";
+const RAW_RUNTIME_STATE: &str = "RAW_RUNTIME_STATE";
+const ZIP: &str = ".zip";
+const VIRTUAL: &str = "__virtual__";
+
+/// Reference to a package; aliased dependencies
+/// also declare the name of the package.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum Reference {
+    Version(String),
+    Alias(String, String),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PackageInformation {
+    package_location: String,
+    #[serde(default)]
+    package_dependencies: Vec<(String, Option<Reference>)>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RuntimeState {
+    #[serde(default)]
+    enable_top_level_fallback: bool,
+    #[serde(default)]
+    fallback_pool: Vec<(String, Option<Reference>)>,
+    package_registry_data:
+        Vec<(Option<String>, Vec<(Option<String>, PackageInformation)>)>,
+}
+
+/// Package name and reference, the top-level project
+/// does not have a name or reference.
+pub type Locator = (Option<String>, Option<String>);
+
+/// Package in the registry.
+struct PnpPackage {
+    /// Location of the package which may be inside a zip archive.
+    location: PathBuf,
+    /// Location of the package on disk.
+    disk_location: PathBuf,
+    /// Dependencies of the package.
+    dependencies: HashMap<String, Option<Reference>>,
+}
+
+/// Resolve module specifiers using a Plug'n'Play manifest.
+pub struct PnpResolver {
+    manifest: PathBuf,
+    extract_dir: PathBuf,
+    packages: HashMap<Locator, PnpPackage>,
+    locations: HashMap<PathBuf, Locator>,
+    top_level_fallback: bool,
+    fallback_pool: HashMap<String, Option<Reference>>,
+    resolver: PackageResolver,
+    extracted: Mutex<HashSet<PathBuf>>,
+}
+
+impl PnpResolver {
+    /// Create a resolver from a manifest file.
+    ///
+    /// Zip archives are extracted to the given directory or to a
+    /// directory in the system temporary directory.
+    pub fn new<P: AsRef<Path>>(
+        manifest: P,
+        conditions: Vec<String>,
+        extract_dir: Option<PathBuf>,
+    ) -> Result<Self> {
+        let manifest = manifest.as_ref().canonicalize().context(format!(
+            "unable to find pnp manifest {}",
+            manifest.as_ref().display()
+        ))?;
+        let project = manifest.parent().unwrap().to_path_buf();
+        let extract_dir = extract_dir
+            .unwrap_or_else(|| std::env::temp_dir().join(TEMP_EXTRACT_DIR));
+        std::fs::create_dir_all(&extract_dir).context(format!(
+            "unable to create directory {}",
+            extract_dir.display()
+        ))?;
+        let extract_dir = extract_dir.canonicalize()?;
+        let state = read_state(&manifest)?;
+
+        let mut packages = HashMap::new();
+        for (name, references) in state.package_registry_data {
+            for (reference, info) in references {
+                let location = normalize(&project.join(&info.package_location));
+                let disk_location = disk_location(&location, &extract_dir);
+                packages.insert(
+                    (name.clone(), reference),
+                    PnpPackage {
+                        location,
+                        disk_location,
+                        dependencies: info
+                            .package_dependencies
+                            .into_iter()
+                            .collect(),
+                    },
+                );
+            }
+        }
+
+        // Virtual packages are preferred over the package they were derived
+        // from and ties are broken by the locator so the owner is stable
+        let mut locations: HashMap<PathBuf, Locator> = HashMap::new();
+        for (locator, package) in packages.iter() {
+            let preferred = match locations.get(&package.disk_location) {
+                Some(current) => {
+                    let other = &packages[current];
+                    (is_virtual(&package.location), locator)
+                        > (is_virtual(&other.location), current)
+                }
+                None => true,
+            };
+            if preferred {
+                locations
+                    .insert(package.disk_location.clone(), locator.clone());
+            }
+        }
+
+        Ok(Self {
+            manifest,
+            extract_dir,
+            packages,
+            locations,
+            top_level_fallback: state.enable_top_level_fallback,
+            fallback_pool: state.fallback_pool.into_iter().collect(),
+            resolver: PackageResolver::new(conditions),
+            extracted: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// Find the manifest for a project by searching the directory
+    /// of a module and its ancestors.
+    pub fn find<P: AsRef<Path>>(file: P) -> Result<PathBuf> {
+        let file = file.as_ref().canonicalize()?;
+        let mut parent = file.parent();
+        while let Some(dir) = parent {
+            for name in MANIFESTS {
+                let manifest = dir.join(name);
+                if manifest.is_file() {
+                    return Ok(manifest);
+                }
+            }
+            parent = dir.parent();
+        }
+        bail!("unable to find a pnp manifest for {}", file.display());
+    }
+
+    /// The manifest file for the resolver.
+    pub fn manifest(&self) -> &Path {
+        &self.manifest
+    }
+
+    /// The directory that zip archives are extracted to.
+    pub fn extract_dir(&self) -> &Path {
+        &self.extract_dir
+    }
+
+    /// Find the locator for the package that owns a file.
+    pub fn owner(&self, file: &Path) -> Option<&Locator> {
+        self.locate(file).map(|(locator, _)| locator)
+    }
+
+    /// Find the package that owns a file.
+    ///
+    /// The name and version are taken from the locator; files owned
+    /// by the top-level project do not belong to a package.
+    pub fn package(&self, file: &Path) -> Option<ModulePackage> {
+        let (locator, package) = self.locate(file)?;
+        let name = locator.0.clone()?;
+        let base = package.disk_location.clone();
+        let version =
+            locator
+                .1
+                .as_deref()
+                .and_then(reference_version)
+                .or_else(|| {
+                    module_package(&base.join(PACKAGE))
+                        .filter(|package| package.base == base)
+                        .and_then(|package| package.version)
+                });
+        Some(ModulePackage {
+            base,
+            name,
+            version,
+        })
+    }
+
+    /// Find the deepest package that contains a file.
+    fn locate(&self, file: &Path) -> Option<(&Locator, &PnpPackage)> {
+        let locator =
+            file.ancestors().find_map(|dir| self.locations.get(dir))?;
+        self.packages.get_key_value(locator)
+    }
+
+    /// Find the locator for a dependency of a package.
+    fn dependency(&self, owner: &Locator, name: &str) -> Result<Locator> {
+        let reference = self
+            .packages
+            .get(owner)
+            .and_then(|package| package.dependencies.get(name))
+            .or_else(|| {
+                if self.top_level_fallback {
+                    self.fallback_pool.get(name).or_else(|| {
+                        self.packages
+                            .get(&(None, None))
+                            .and_then(|root| root.dependencies.get(name))
+                    })
+                } else {
+                    None
+                }
+            });
+
+        match reference {
+            Some(Some(Reference::Version(reference))) => {
+                Ok((Some(name.to_string()), Some(reference.clone())))
+            }
+            Some(Some(Reference::Alias(alias, reference))) => {
+                Ok((Some(alias.clone()), Some(reference.clone())))
+            }
+            Some(None) => bail!("missing peer dependency {}", name),
+            None => bail!(
+                "{} is not a dependency of {}",
+                name,
+                owner.0.as_deref().unwrap_or("the project")
+            ),
+        }
+    }
+
+    /// Get the directory on disk for a package extracting
+    /// the zip archive for the package when necessary.
+    fn package_dir(&self, package: &PnpPackage) -> Result<PathBuf> {
+        if let Some(archive) = zip_archive(&devirtualize(&package.location)) {
+            let mut extracted = self.extracted.lock().unwrap();
+            if !extracted.contains(&archive) {
+                let target = extract_target(&archive, &self.extract_dir);
+                if !target.is_dir() {
+                    extract(&archive, &target)?;
+                }
+                extracted.insert(archive);
+            }
+        }
+        Ok(package.disk_location.clone())
+    }
+}
+
+impl Resolve for PnpResolver {
+    fn resolve(
+        &self,
+        base: &FileName,
+        specifier: &str,
+    ) -> Result<FileName, Error> {
+        let file = match base {
+            FileName::Real(file) => file,
+            _ => return self.resolver.resolve(base, specifier),
+        };
+
+        if !is_bare_specifier(specifier)
+            || specifier.starts_with('#')
+            || is_builtin_module(specifier)
+        {
+            return self.resolver.resolve(base, specifier);
+        }
+
+        let (name, subpath) = split_specifier(specifier)
+            .context(format!("invalid specifier {}", specifier))?;
+        let owner = self.owner(file).cloned().unwrap_or((None, None));
+        let locator = self.dependency(&owner, &name)?;
+        let package = self.packages.get(&locator).context(format!(
+            "package {} is not in the pnp registry",
+            specifier
+        ))?;
+        let dir = self.package_dir(package)?;
+        match self.resolver.resolve_package(&dir, &subpath)? {
+            FileName::Real(path) => {
+                let path = path.canonicalize().unwrap_or(path);
+                Ok(FileName::Real(path))
+            }
+            file_name => Ok(file_name),
+        }
+    }
+}
+
+/// Get the version from a package reference such as `npm:1.0.0`
+/// or `virtual:<hash>#npm:1.0.0`.
+fn reference_version(reference: &str) -> Option<String> {
+    let reference = reference.rsplit('#').next()?;
+    reference.strip_prefix("npm:").map(|v| v.to_string())
+}
+
+/// Read the runtime state from a manifest.
+fn read_state(manifest: &Path) -> Result<RuntimeState> {
+    let is_json = manifest.extension().map(|e| e == "json").unwrap_or(false);
+    let contents = if is_json {
+        std::fs::read_to_string(manifest)?
+    } else {
+        let (_, _, module) = load_file(manifest, None)?;
+        let mut visitor = RuntimeStateVisitor { state: None };
+        module.visit_children_with(&mut visitor);
+        visitor.state.context(format!(
+            "unable to find runtime state in {}",
+            manifest.display()
+        ))?
+    };
+    Ok(serde_json::from_str(&contents).context(format!(
+        "failed to parse pnp data in {}",
+        manifest.display()
+    ))?)
+}
+
+/// Find the inlined runtime state in a `.pnp.cjs` file.
+///
+/// The state is assigned to `RAW_RUNTIME_STATE` or passed
+/// to a call to `JSON.parse()` in older versions.
+struct RuntimeStateVisitor {
+    state: Option<String>,
+}
+
+impl Visit for RuntimeStateVisitor {
+    fn visit_var_declarator(&mut self, n: &VarDeclarator, _: &dyn Node) {
+        if let (Pat::Ident(id), Some(init)) = (&n.name, &n.init) {
+            if let Expr::Lit(Lit::Str(s)) = &**init {
+                if id.id.sym.as_ref() == RAW_RUNTIME_STATE {
+                    self.state = Some(s.value.to_string());
+                    return;
+                }
+            }
+        }
+        n.visit_children_with(self);
+    }
+
+    fn visit_call_expr(&mut self, n: &CallExpr, _: &dyn Node) {
+        if self.state.is_none() {
+            if let ExprOrSuper::Expr(callee) = &n.callee {
+                if let Expr::Member(member) = &**callee {
+                    if let (ExprOrSuper::Expr(obj), Expr::Ident(prop)) =
+                        (&member.obj, &*member.prop)
+                    {
+                        if let Expr::Ident(obj) = &**obj {
+                            if obj.sym.as_ref() == "JSON"
+                                && prop.sym.as_ref() == "parse"
+                            {
+                                if let Some(Expr::Lit(Lit::Str(s))) =
+                                    n.args.get(0).map(|a| &*a.expr)
+                                {
+                                    self.state = Some(s.value.to_string());
+                                    return;
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        n.visit_children_with(self);
+    }
+}
+
+/// Normalize a path removing `.` and `..` components.
+fn normalize(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                result.pop();
+            }
+            _ => result.push(component),
+        }
+    }
+    result
+}
+
+/// Get the zip archive that contains a location.
+fn zip_archive(location: &Path) -> Option<PathBuf> {
+    let mut archive = PathBuf::new();
+    for component in location.components() {
+        archive.push(component);
+        if archive.to_string_lossy().ends_with(ZIP) {
+            return Some(archive);
+        }
+    }
+    None
+}
+
+/// Determine the location on disk for a package.
+///
+/// Virtual locations are mapped to the location they were derived
+/// from and locations in a zip archive are mapped to the directory
+/// where the archive is extracted.
+fn disk_location(location: &Path, extract_dir: &Path) -> PathBuf {
+    let location = devirtualize(location);
+    if let Some(archive) = zip_archive(&location) {
+        let inner = location.strip_prefix(&archive).unwrap();
+        extract_target(&archive, extract_dir).join(inner)
+    } else {
+        location
+    }
+}
+
+/// Determine if a location is a virtual location.
+fn is_virtual(location: &Path) -> bool {
+    location.components().any(|c| c.as_os_str() == VIRTUAL)
+}
+
+/// Map a virtual path (`__virtual__/<hash>/<depth>/<path>`)
+/// to the path it was derived from.
+fn devirtualize(location: &Path) -> PathBuf {
+    let components: Vec<Component> = location.components().collect();
+    if let Some(index) =
+        components.iter().position(|c| c.as_os_str() == VIRTUAL)
+    {
+        let depth = components
+            .get(index + 2)
+            .and_then(|c| c.as_os_str().to_str())
+            .and_then(|s| s.parse::<usize>().ok());
+        if let Some(depth) = depth {
+            let mut result: PathBuf = components[..index].iter().collect();
+            for _ in 0..depth {
+                result.pop();
+            }
+            for component in components.iter().skip(index + 3) {
+                result.push(component);
+            }
+            return result;
+        }
+    }
+    location.to_path_buf()
+}
+
+/// Directory that a zip archive is extracted to.
+fn extract_target(archive: &Path, extract_dir: &Path) -> PathBuf {
+    extract_dir.join(archive.file_stem().unwrap())
+}
+
+/// Extract a zip archive to a directory.
+///
+/// Files are extracted to a temporary directory first so that a
+/// partially extracted archive is never used; when another resolver
+/// extracts the same archive first its files are used.
+fn extract(archive: &Path, target: &Path) -> Result<()> {
+    log::debug!("Extract {} to {}", archive.display(), target.display());
+    let file = File::open(archive)
+        .context(format!("unable to open {}", archive.display()))?;
+    let mut zip = zip::ZipArchive::new(file)?;
+    let temp = target.with_file_name(format!(
+        ".{}.{}-{}.tmp",
+        target.file_name().unwrap().to_string_lossy(),
+        std::process::id(),
+        EXTRACTING.fetch_add(1, SeqCst)
+    ));
+    std::fs::create_dir_all(&temp)?;
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index)?;
+        let name = match entry.enclosed_name() {
+            Some(name) => name.to_path_buf(),
+            None => continue,
+        };
+        let path = temp.join(name);
+        if entry.is_dir() {
+            std::fs::create_dir_all(&path)?;
+        } else {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut output = File::create(&path)?;
+            std::io::copy(&mut entry, &mut output)?;
+        }
+    }
+    if let Err(e) = std::fs::rename(&temp, target) {
+        let _ = std::fs::remove_dir_all(&temp);
+        if !target.is_dir() {
+            return Err(e).context(format!(
+                "unable to extract {} to {}",
+                archive.display(),
+                target.display()
+            ));
+        }
+    }
+    Ok(())
+}
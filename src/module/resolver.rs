@@ -0,0 +1,529 @@
+//! Resolver that honors the package.json `exports` and `imports` fields.
+//!
+//! Bare specifiers for packages that declare an `exports` field and
+//! `#` specifiers for subpath imports are resolved against the package
+//! configuration using the conditions assigned to the resolver, all
+//! other specifiers are resolved using the node modules algorithm.
+//!
+//! Conditions are matched in the order they are declared by the package;
+//! the `default` condition always matches. A malformed `package.json` is
+//! an error when it is used to resolve a specifier and otherwise skipped.
+//!
+//! For TypeScript projects bare specifiers are first resolved using the
+//! `paths` declared in `tsconfig.json` and relative specifiers that do not
+//! exist are resolved to TypeScript files, for example `./util.js` may
+//! refer to `./util.ts`.
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Error, Result};
+use dashmap::{DashMap, DashSet};
+use indexmap::IndexMap;
+use serde::Deserialize;
+
+use swc_common::FileName;
+use swc_ecma_ast::TargetEnv;
+use swc_ecma_loader::{resolve::Resolve, resolvers::node::NodeModulesResolver};
+
+use super::tsconfig::TsConfig;
+use crate::swc_utils::TYPESCRIPT_EXTENSIONS;
+
+const PACKAGE: &str = "package.json";
+const NODE_MODULES: &str = "node_modules";
+const DEFAULT: &str = "default";
+const WILDCARD: char = '*';
+
+/// Conditions for modules loaded using `require()`.
+pub const REQUIRE_CONDITIONS: &[&str] = &["node", "require"];
+
+/// Conditions for modules loaded using `import`.
+pub const IMPORT_CONDITIONS: &[&str] = &["node", "import"];
+
+/// Target for an entry in the `exports` or `imports` map.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Target {
+    Null,
+    Path(String),
+    List(Vec<Target>),
+    Map(IndexMap<String, Target>),
+}
+
+#[derive(Debug, Deserialize)]
+struct PackageJson {
+    name: Option<String>,
+    exports: Option<Target>,
+    imports: Option<IndexMap<String, Target>>,
+}
+
+/// Package configuration and the directory containing the package.
+struct Package {
+    dir: PathBuf,
+    json: PackageJson,
+}
+
+impl Package {
+    fn load(dir: &Path) -> Result<Option<Self>> {
+        let file = dir.join(PACKAGE);
+        if !file.is_file() {
+            return Ok(None);
+        }
+        let contents = std::fs::read_to_string(&file)?;
+        let json: PackageJson = serde_json::from_str(&contents)
+            .context(format!("failed to parse {}", file.display()))?;
+        Ok(Some(Self {
+            dir: dir.to_path_buf(),
+            json,
+        }))
+    }
+}
+
+/// Resolve module specifiers using package `exports` and `imports`.
+pub struct PackageResolver {
+    conditions: Vec<String>,
+    inner: NodeModulesResolver,
+    tsconfig: DashMap<PathBuf, Option<Arc<TsConfig>>>,
+    malformed: DashSet<PathBuf>,
+}
+
+impl PackageResolver {
+    /// Create a resolver for a set of conditions.
+    pub fn new(conditions: Vec<String>) -> Self {
+        Self {
+            conditions,
+            inner: NodeModulesResolver::new(
+                TargetEnv::Node,
+                Default::default(),
+            ),
+            tsconfig: DashMap::new(),
+            malformed: DashSet::new(),
+        }
+    }
+
+    /// The conditions used to resolve specifiers.
+    pub fn conditions(&self) -> &[String] {
+        &self.conditions
+    }
+
+    fn resolve_specifier(
+        &self,
+        base: &FileName,
+        specifier: &str,
+    ) -> Result<FileName> {
+        if let FileName::Real(file) = base {
+            let dir = if file.is_file() {
+                file.parent()
+            } else {
+                Some(&**file)
+            };
+            if let Some(dir) = dir {
+                if specifier.starts_with('#') {
+                    return self.resolve_imports(dir, specifier);
+                } else if is_bare_specifier(specifier) {
+                    if let Some(file_name) = self.resolve_paths(dir, specifier)
+                    {
+                        return Ok(file_name);
+                    }
+                    if let Some(file_name) =
+                        self.resolve_exports(dir, specifier)?
+                    {
+                        return Ok(file_name);
+                    }
+                } else {
+                    match self.inner.resolve(base, specifier) {
+                        Ok(file_name) => return Ok(file_name),
+                        Err(e) => {
+                            return typescript_file(&dir.join(specifier))
+                                .map(FileName::Real)
+                                .ok_or(e)
+                        }
+                    }
+                }
+            }
+        }
+        self.inner.resolve(base, specifier)
+    }
+
+    /// Resolve a bare specifier using the `paths` declared in the
+    /// nearest `tsconfig.json`.
+    ///
+    /// Modules in `node_modules` do not use the project configuration.
+    fn resolve_paths(&self, dir: &Path, specifier: &str) -> Option<FileName> {
+        if dir.components().any(|c| c.as_os_str() == NODE_MODULES) {
+            return None;
+        }
+        let tsconfig = self
+            .tsconfig
+            .entry(dir.to_path_buf())
+            .or_insert_with(|| match TsConfig::find(dir) {
+                Ok(tsconfig) => tsconfig.map(Arc::new),
+                Err(e) => {
+                    log::warn!("{:#}", e);
+                    None
+                }
+            })
+            .clone()?;
+        tsconfig.candidates(specifier).iter().find_map(|candidate| {
+            let dir = candidate.parent()?;
+            let name = candidate.file_name()?.to_str()?;
+            self.inner
+                .resolve(
+                    &FileName::Real(dir.to_path_buf()),
+                    &format!("./{}", name),
+                )
+                .ok()
+                .or_else(|| typescript_file(candidate).map(FileName::Real))
+        })
+    }
+
+    /// Resolve a bare specifier using the `exports` of the package.
+    ///
+    /// Returns `None` when the package does not declare `exports`.
+    fn resolve_exports(
+        &self,
+        dir: &Path,
+        specifier: &str,
+    ) -> Result<Option<FileName>> {
+        let (name, subpath) = match split_specifier(specifier) {
+            Some(parts) => parts,
+            None => return Ok(None),
+        };
+
+        let package = match self.find_package(dir, &name)? {
+            Some(package) => package,
+            None => return Ok(None),
+        };
+        self.resolve_package_exports(&package, &subpath)
+    }
+
+    /// Resolve a subpath of a package in a directory.
+    ///
+    /// The `exports` of the package are used when declared otherwise
+    /// the subpath is resolved relative to the package directory.
+    pub(crate) fn resolve_package(
+        &self,
+        dir: &Path,
+        subpath: &str,
+    ) -> Result<FileName> {
+        if let Some(package) = Package::load(dir)? {
+            if let Some(file_name) =
+                self.resolve_package_exports(&package, subpath)?
+            {
+                return Ok(file_name);
+            }
+        }
+        let relative = if subpath == "." { "./" } else { subpath };
+        self.inner
+            .resolve(&FileName::Real(dir.to_path_buf()), relative)
+    }
+
+    /// Resolve a subpath using the `exports` of a package.
+    ///
+    /// Returns `None` when the package does not declare `exports`.
+    fn resolve_package_exports(
+        &self,
+        package: &Package,
+        subpath: &str,
+    ) -> Result<Option<FileName>> {
+        let exports = match &package.json.exports {
+            Some(Target::Null) | None => return Ok(None),
+            Some(exports) => exports,
+        };
+
+        let resolved = match exports {
+            Target::Map(map) if map.keys().any(|k| k.starts_with('.')) => {
+                self.resolve_map(&package.dir, map, subpath, false)?
+            }
+            _ if subpath == "." => {
+                self.resolve_target(&package.dir, exports, None, false)?
+            }
+            _ => None,
+        };
+
+        match resolved {
+            Some(file_name) => Ok(Some(file_name)),
+            None => bail!(
+                "subpath {} is not exported by {} for conditions {:?}",
+                subpath,
+                package.dir.join(PACKAGE).display(),
+                self.conditions
+            ),
+        }
+    }
+
+    /// Resolve a `#` specifier using the `imports` of the
+    /// package that contains a module.
+    fn resolve_imports(&self, dir: &Path, specifier: &str) -> Result<FileName> {
+        let mut parent = Some(dir);
+        while let Some(dir) = parent {
+            if let Some(package) = Package::load(dir)? {
+                if let Some(imports) = &package.json.imports {
+                    if let Some(file_name) = self.resolve_map(
+                        &package.dir,
+                        imports,
+                        specifier,
+                        true,
+                    )? {
+                        return Ok(file_name);
+                    }
+                }
+                bail!(
+                    "import {} is not defined by {} for conditions {:?}",
+                    specifier,
+                    package.dir.join(PACKAGE).display(),
+                    self.conditions
+                );
+            }
+            parent = dir.parent();
+        }
+        bail!("unable to find package.json for import {}", specifier);
+    }
+
+    /// Find a package in the `node_modules` directories of a directory
+    /// and its ancestors, a package may also refer to itself by name.
+    fn find_package(&self, dir: &Path, name: &str) -> Result<Option<Package>> {
+        let mut parent = Some(dir);
+        let mut scope_checked = false;
+        while let Some(dir) = parent {
+            if !scope_checked {
+                // The package containing the module is only used when it
+                // refers to itself so a malformed package.json is skipped
+                match Package::load(dir) {
+                    Ok(Some(package)) => {
+                        scope_checked = true;
+                        if package.json.name.as_deref() == Some(name)
+                            && package.json.exports.is_some()
+                        {
+                            return Ok(Some(package));
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        scope_checked = true;
+                        if self.malformed.insert(dir.to_path_buf()) {
+                            log::warn!("{:#}", e);
+                        }
+                    }
+                }
+            }
+            let candidate = dir.join(NODE_MODULES).join(name);
+            if let Some(package) = Package::load(&candidate)? {
+                return Ok(Some(package));
+            }
+            parent = dir.parent();
+        }
+        Ok(None)
+    }
+
+    /// Resolve a key in an `exports` or `imports` map.
+    ///
+    /// Exact matches take precedence, otherwise the pattern with
+    /// the longest prefix before the wildcard is used.
+    fn resolve_map(
+        &self,
+        dir: &Path,
+        map: &IndexMap<String, Target>,
+        key: &str,
+        is_imports: bool,
+    ) -> Result<Option<FileName>> {
+        if let Some(target) = map.get(key) {
+            if !key.contains(WILDCARD) {
+                return self.resolve_target(dir, target, None, is_imports);
+            }
+        }
+
+        let mut best: Option<(&str, &Target, String)> = None;
+        for (pattern, target) in map.iter() {
+            if let Some(index) = pattern.find(WILDCARD) {
+                let (prefix, suffix) =
+                    (&pattern[..index], &pattern[index + 1..]);
+                if key.len() >= prefix.len() + suffix.len()
+                    && key.starts_with(prefix)
+                    && key.ends_with(suffix)
+                {
+                    let better = match &best {
+                        Some((current, _, _)) => is_better(pattern, current),
+                        None => true,
+                    };
+                    if better {
+                        let value =
+                            key[prefix.len()..key.len() - suffix.len()].into();
+                        best = Some((pattern, target, value));
+                    }
+                }
+            }
+        }
+
+        if let Some((_, target, value)) = best {
+            return self.resolve_target(dir, target, Some(&value), is_imports);
+        }
+        Ok(None)
+    }
+
+    /// Resolve a target, returns `None` when the target does not
+    /// match the conditions or is excluded.
+    fn resolve_target(
+        &self,
+        dir: &Path,
+        target: &Target,
+        substitution: Option<&str>,
+        is_imports: bool,
+    ) -> Result<Option<FileName>> {
+        match target {
+            Target::Null => Ok(None),
+            Target::Path(path) => {
+                let path = match substitution {
+                    Some(value) => path.replace(WILDCARD, value),
+                    None => path.to_string(),
+                };
+                if let Some(relative) = path.strip_prefix("./") {
+                    let file = join_relative(dir, relative)
+                        .context(format!("invalid package target {}", path))?;
+                    if !file.is_file() {
+                        bail!(
+                            "package target {} does not exist",
+                            file.display()
+                        );
+                    }
+                    Ok(Some(FileName::Real(file)))
+                } else if is_imports {
+                    // Imports may map to another package
+                    let base = FileName::Real(dir.join(PACKAGE));
+                    Ok(Some(self.resolve(&base, &path)?))
+                } else {
+                    bail!("invalid package target {}", path)
+                }
+            }
+            Target::List(targets) => {
+                for target in targets {
+                    if let Ok(Some(file_name)) = self.resolve_target(
+                        dir,
+                        target,
+                        substitution,
+                        is_imports,
+                    ) {
+                        return Ok(Some(file_name));
+                    }
+                }
+                Ok(None)
+            }
+            Target::Map(conditions) => {
+                for (condition, target) in conditions.iter() {
+                    if condition == DEFAULT
+                        || self.conditions.iter().any(|c| c == condition)
+                    {
+                        if let Some(file_name) = self.resolve_target(
+                            dir,
+                            target,
+                            substitution,
+                            is_imports,
+                        )? {
+                            return Ok(Some(file_name));
+                        }
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Default for PackageResolver {
+    fn default() -> Self {
+        Self::new(REQUIRE_CONDITIONS.iter().map(|c| c.to_string()).collect())
+    }
+}
+
+impl Resolve for PackageResolver {
+    /// Resolve a specifier, real paths are canonicalized so that packages
+    /// linked into `node_modules` (workspaces or a `.pnpm` store) have
+    /// a single location no matter which path was used to reach them.
+    fn resolve(
+        &self,
+        base: &FileName,
+        specifier: &str,
+    ) -> Result<FileName, Error> {
+        match self.resolve_specifier(base, specifier)? {
+            FileName::Real(path) => {
+                let path = path.canonicalize().unwrap_or(path);
+                Ok(FileName::Real(path))
+            }
+            file_name => Ok(file_name),
+        }
+    }
+}
+
+pub(crate) fn is_bare_specifier(specifier: &str) -> bool {
+    !specifier.starts_with('.')
+        && !specifier.starts_with('/')
+        && !specifier.contains(':')
+}
+
+/// Split a bare specifier into the package name and the subpath.
+pub(crate) fn split_specifier(specifier: &str) -> Option<(String, String)> {
+    let mut parts = specifier.splitn(3, '/');
+    let first = parts.next()?;
+    let name = if first.starts_with('@') {
+        format!("{}/{}", first, parts.next()?)
+    } else {
+        first.to_string()
+    };
+    let rest = &specifier[name.len()..];
+    let subpath = if rest.is_empty() {
+        ".".to_string()
+    } else {
+        format!(".{}", rest)
+    };
+    Some((name, subpath))
+}
+
+/// Join a relative target path to a package directory rejecting
+/// paths that would escape the package.
+fn join_relative(dir: &Path, relative: &str) -> Option<PathBuf> {
+    let mut path = dir.to_path_buf();
+    for segment in relative.split('/') {
+        match segment {
+            "" | "." | ".." | NODE_MODULES => return None,
+            _ => path.push(segment),
+        }
+    }
+    Some(path)
+}
+
+/// Find the TypeScript file for a path that does not exist.
+///
+/// JavaScript extensions are mapped to the TypeScript equivalent
+/// otherwise TypeScript extensions and index files are tried.
+fn typescript_file(path: &Path) -> Option<PathBuf> {
+    let candidates =
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("js") => {
+                vec![path.with_extension("ts"), path.with_extension("tsx")]
+            }
+            Some("jsx") => vec![path.with_extension("tsx")],
+            Some("mjs") => vec![path.with_extension("mts")],
+            Some("cjs") => vec![path.with_extension("cts")],
+            _ => TYPESCRIPT_EXTENSIONS
+                .iter()
+                .map(|extension| {
+                    let mut file = path.as_os_str().to_owned();
+                    file.push(".");
+                    file.push(extension);
+                    PathBuf::from(file)
+                })
+                .chain(TYPESCRIPT_EXTENSIONS.iter().map(|extension| {
+                    path.join("index").with_extension(extension)
+                }))
+                .collect(),
+        };
+    candidates.into_iter().find(|file| file.is_file())
+}
+
+/// Compare patterns so that the longest prefix before the
+/// wildcard wins followed by the longest pattern.
+pub(crate) fn is_better(pattern: &str, current: &str) -> bool {
+    let prefix = pattern.find(WILDCARD).unwrap_or(pattern.len());
+    let current_prefix = current.find(WILDCARD).unwrap_or(current.len());
+    prefix > current_prefix
+        || (prefix == current_prefix && pattern.len() > current.len())
+}
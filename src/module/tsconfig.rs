@@ -0,0 +1,264 @@
+//! Support for the `paths` aliases declared in `tsconfig.json`.
+//!
+//! The nearest `tsconfig.json` to an importing module is used and the
+//! configurations it `extends` are followed; relative `extends` paths
+//! are resolved from the extending file and other values are resolved
+//! from `node_modules`. Targets are relative to `baseUrl` when declared
+//! otherwise they are relative to the directory that contains the
+//! configuration file that declares the `paths`.
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use indexmap::IndexMap;
+use serde::Deserialize;
+
+use super::resolver::is_better;
+
+const TSCONFIG: &str = "tsconfig.json";
+const NODE_MODULES: &str = "node_modules";
+const WILDCARD: char = '*';
+
+/// Maximum depth when following `extends`.
+const MAX_EXTENDS: usize = 32;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CompilerOptions {
+    base_url: Option<String>,
+    paths: Option<IndexMap<String, Vec<String>>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Extends {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TsConfigJson {
+    extends: Option<Extends>,
+    #[serde(default)]
+    compiler_options: CompilerOptions,
+}
+
+/// Options inherited from the configurations that are extended.
+#[derive(Default)]
+struct Inherited {
+    base_url: Option<PathBuf>,
+    /// Paths and the directory of the file that declares them.
+    paths: Option<(PathBuf, IndexMap<String, Vec<String>>)>,
+    files: Vec<PathBuf>,
+}
+
+/// Path aliases for a TypeScript project.
+#[derive(Debug)]
+pub struct TsConfig {
+    base: PathBuf,
+    paths: IndexMap<String, Vec<String>>,
+    files: Vec<PathBuf>,
+}
+
+impl TsConfig {
+    /// Find the nearest `tsconfig.json` for a directory.
+    pub fn find(dir: &Path) -> Result<Option<Self>> {
+        match Self::find_file(dir) {
+            Some(file) => Ok(Some(Self::load(&file)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Find the path to the nearest `tsconfig.json` for a directory.
+    pub fn find_file(dir: &Path) -> Option<PathBuf> {
+        dir.ancestors()
+            .map(|dir| dir.join(TSCONFIG))
+            .find(|file| file.is_file())
+    }
+
+    /// Load the path aliases from a `tsconfig.json` file.
+    pub fn load(file: &Path) -> Result<Self> {
+        let options = Self::inherit(file, 0)?;
+        let (dir, paths) = options.paths.unwrap_or_else(|| {
+            (file.parent().unwrap().to_path_buf(), Default::default())
+        });
+        Ok(Self {
+            base: options.base_url.unwrap_or(dir),
+            paths,
+            files: options.files,
+        })
+    }
+
+    /// The configuration files that were loaded.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Load the options from a file and the files it extends, options
+    /// declared in a file override the options that it extends.
+    fn inherit(file: &Path, depth: usize) -> Result<Inherited> {
+        if depth > MAX_EXTENDS {
+            bail!("too many extends in {}", file.display());
+        }
+
+        let contents = std::fs::read_to_string(file)?;
+        let json: TsConfigJson =
+            serde_json::from_str(&strip_json(&contents))
+                .context(format!("failed to parse {}", file.display()))?;
+        let dir = file.parent().unwrap();
+
+        let extends = match json.extends {
+            Some(Extends::One(extends)) => vec![extends],
+            Some(Extends::Many(extends)) => extends,
+            None => vec![],
+        };
+
+        let mut options: Inherited = Default::default();
+        for extends in extends {
+            let parent = find_extends(dir, &extends).context(format!(
+                "unable to find {} extended by {}",
+                extends,
+                file.display()
+            ))?;
+            let mut parent = Self::inherit(&parent, depth + 1)?;
+            if parent.base_url.is_some() {
+                options.base_url = parent.base_url;
+            }
+            if parent.paths.is_some() {
+                options.paths = parent.paths;
+            }
+            options.files.append(&mut parent.files);
+        }
+
+        let compiler_options = json.compiler_options;
+        if let Some(base_url) = compiler_options.base_url {
+            options.base_url = Some(dir.join(base_url));
+        }
+        if let Some(paths) = compiler_options.paths {
+            options.paths = Some((dir.to_path_buf(), paths));
+        }
+        options.files.push(file.to_path_buf());
+        Ok(options)
+    }
+
+    /// Get the candidate paths for a specifier in the order
+    /// they should be tried.
+    ///
+    /// Exact matches take precedence, otherwise the pattern with
+    /// the longest prefix before the wildcard is used.
+    pub fn candidates(&self, specifier: &str) -> Vec<PathBuf> {
+        if let Some(targets) = self.paths.get(specifier) {
+            return targets.iter().map(|t| self.base.join(t)).collect();
+        }
+
+        let mut best: Option<(&str, &Vec<String>, &str)> = None;
+        for (pattern, targets) in self.paths.iter() {
+            if let Some(index) = pattern.find(WILDCARD) {
+                let (prefix, suffix) =
+                    (&pattern[..index], &pattern[index + 1..]);
+                if specifier.len() >= prefix.len() + suffix.len()
+                    && specifier.starts_with(prefix)
+                    && specifier.ends_with(suffix)
+                {
+                    let better = match &best {
+                        Some((current, _, _)) => is_better(pattern, current),
+                        None => true,
+                    };
+                    if better {
+                        let value = &specifier
+                            [prefix.len()..specifier.len() - suffix.len()];
+                        best = Some((pattern, targets, value));
+                    }
+                }
+            }
+        }
+
+        match best {
+            Some((_, targets, value)) => targets
+                .iter()
+                .map(|t| self.base.join(t.replace(WILDCARD, value)))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Find the file for an `extends` value.
+///
+/// The value may omit the `.json` extension or name a package
+/// directory that contains a `tsconfig.json` file.
+fn find_extends(dir: &Path, extends: &str) -> Option<PathBuf> {
+    let candidates: Vec<PathBuf> =
+        if extends.starts_with('.') || Path::new(extends).is_absolute() {
+            vec![dir.join(extends)]
+        } else {
+            dir.ancestors()
+                .map(|dir| dir.join(NODE_MODULES).join(extends))
+                .collect()
+        };
+    candidates.into_iter().find_map(|candidate| {
+        let mut json = candidate.clone().into_os_string();
+        json.push(".json");
+        vec![
+            candidate.clone(),
+            PathBuf::from(json),
+            candidate.join(TSCONFIG),
+        ]
+        .into_iter()
+        .find(|file| file.is_file())
+    })
+}
+
+/// Remove the comments and trailing commas that are
+/// permitted in `tsconfig.json` files.
+fn strip_json(contents: &str) -> String {
+    let mut output = String::with_capacity(contents.len());
+    let mut chars = contents.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            output.push(c);
+            if c == '\\' {
+                if let Some(escaped) = chars.next() {
+                    output.push(escaped);
+                }
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                output.push(c);
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                while let Some(c) = chars.next() {
+                    if c == '\n' {
+                        output.push(c);
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                while let Some(c) = chars.next() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            '}' | ']' => {
+                let trimmed = output.trim_end().len();
+                if output[..trimmed].ends_with(',') {
+                    output.truncate(trimmed - 1);
+                }
+                output.push(c);
+            }
+            _ => output.push(c),
+        }
+    }
+    output
+}
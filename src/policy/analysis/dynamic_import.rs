@@ -1,12 +1,20 @@
 //! Helper functions to detect calls to `require` or `import`.
 
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize};
+
 use swc_atoms::JsWord;
+use swc_common::{SourceMap, Span, DUMMY_SP};
 use swc_ecma_ast::*;
+use swc_ecma_visit::{Node, Visit, VisitWith};
 
 use super::member_expr::walk;
 
 const REQUIRE: &str = "require";
 const IMPORT: &str = "import";
+const RESOLVE: &str = "resolve";
+const WILDCARD: &str = "*";
 
 /// Import that is a function call.
 pub struct DynamicCall<'a> {
@@ -96,3 +104,197 @@ fn is_simple_call<'a>(
     }
     None
 }
+
+/// Call to `require()` or `import()` with a specifier that cannot
+/// be resolved statically.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DynamicImport {
+    /// Function name.
+    #[serde(rename = "function", deserialize_with = "deserialize_fn_name")]
+    pub fn_name: &'static str,
+    /// Glob pattern for the specifier when it has constant parts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    /// The line number (one-based) of the call.
+    pub line: usize,
+    /// The location of the call.
+    ///
+    /// Dynamic imports loaded from the analysis cache do not
+    /// have a location.
+    #[serde(skip, default = "dummy_span")]
+    pub span: Span,
+}
+
+fn deserialize_fn_name<'de, D>(
+    deserializer: D,
+) -> Result<&'static str, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let name = String::deserialize(deserializer)?;
+    match &name[..] {
+        REQUIRE => Ok(REQUIRE),
+        IMPORT => Ok(IMPORT),
+        _ => Err(serde::de::Error::custom(format!(
+            "unknown dynamic import function {}",
+            name
+        ))),
+    }
+}
+
+fn dummy_span() -> Span {
+    DUMMY_SP
+}
+
+impl fmt::Display for DynamicImport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(pattern) = &self.pattern {
+            write!(f, "dynamic call to {}() matching {}", self.fn_name, pattern)
+        } else {
+            write!(f, "unresolvable dynamic call to {}()", self.fn_name)
+        }
+    }
+}
+
+/// Visit a module and find all calls to `require()` or `import()`
+/// that do not use a string literal specifier.
+pub struct DynamicImportAnalysis<'a> {
+    source_map: &'a SourceMap,
+    /// The detected dynamic imports.
+    pub imports: Vec<DynamicImport>,
+}
+
+impl<'a> DynamicImportAnalysis<'a> {
+    /// Create a dynamic import analysis.
+    ///
+    /// The source map is used to determine line numbers.
+    pub fn new(source_map: &'a SourceMap) -> Self {
+        Self {
+            source_map,
+            imports: Vec::new(),
+        }
+    }
+}
+
+impl Visit for DynamicImportAnalysis<'_> {
+    fn visit_call_expr(&mut self, n: &CallExpr, _: &dyn Node) {
+        if let Some((fn_name, pattern)) = is_dynamic_call(n) {
+            let loc = self.source_map.lookup_char_pos(n.span.lo);
+            self.imports.push(DynamicImport {
+                fn_name,
+                pattern,
+                line: loc.line,
+                span: n.span,
+            });
+        }
+        n.visit_children_with(self);
+    }
+}
+
+/// Detect a call to `require()` or `import()` where the argument
+/// is not a string literal.
+///
+/// Template literals and string concatenation with constant parts
+/// are converted to a glob pattern.
+pub fn is_dynamic_call(
+    call: &CallExpr,
+) -> Option<(&'static str, Option<String>)> {
+    let fn_name = if let ExprOrSuper::Expr(n) = &call.callee {
+        match &**n {
+            Expr::Ident(id) if id.sym.as_ref() == REQUIRE => REQUIRE,
+            Expr::Ident(id) if id.sym.as_ref() == IMPORT => IMPORT,
+            _ => return None,
+        }
+    } else {
+        return None;
+    };
+
+    let arg = call.args.get(0)?;
+    if arg.spread.is_some() {
+        return Some((fn_name, None));
+    }
+
+    let mut parts = Vec::new();
+    specifier_parts(&*arg.expr, &mut parts);
+
+    // Specifier is constant so it is not dynamic
+    if parts.iter().all(|p| p.is_some()) {
+        return None;
+    }
+
+    Some((fn_name, glob_pattern(parts)))
+}
+
+/// Collect the constant parts of a specifier expression,
+/// non-constant parts are represented as `None`.
+fn specifier_parts(n: &Expr, parts: &mut Vec<Option<String>>) {
+    match n {
+        Expr::Lit(Lit::Str(s)) => parts.push(Some(s.value.to_string())),
+        Expr::Tpl(tpl) => {
+            for (i, quasi) in tpl.quasis.iter().enumerate() {
+                parts.push(Some(quasi.raw.value.to_string()));
+                if i < tpl.exprs.len() {
+                    specifier_parts(&*tpl.exprs[i], parts);
+                }
+            }
+        }
+        Expr::Bin(bin) if bin.op == BinaryOp::Add => {
+            specifier_parts(&*bin.left, parts);
+            specifier_parts(&*bin.right, parts);
+        }
+        Expr::Paren(n) => specifier_parts(&*n.expr, parts),
+        _ => parts.push(None),
+    }
+}
+
+/// Convert specifier parts to a glob pattern.
+///
+/// Returns `None` when the pattern has no constant parts.
+fn glob_pattern(parts: Vec<Option<String>>) -> Option<String> {
+    let mut pattern = String::new();
+    for part in parts {
+        match part {
+            Some(value) => pattern.push_str(&value),
+            None => {
+                if !pattern.ends_with(WILDCARD) {
+                    pattern.push_str(WILDCARD);
+                }
+            }
+        }
+    }
+
+    if pattern == WILDCARD {
+        None
+    } else {
+        Some(pattern)
+    }
+}
+
+/// Detect an expression that is a call to `require.resolve()`.
+///
+/// The call must be a simple call expression (single string argument).
+pub fn is_require_resolve_expr(n: &Expr) -> Option<&JsWord> {
+    if let Expr::Call(call) = n {
+        if let (ExprOrSuper::Expr(callee), Some(arg)) =
+            (&call.callee, call.args.get(0))
+        {
+            if let Expr::Member(member) = &**callee {
+                if let (ExprOrSuper::Expr(obj), Expr::Ident(prop)) =
+                    (&member.obj, &*member.prop)
+                {
+                    if let Expr::Ident(obj) = &**obj {
+                        if !member.computed
+                            && obj.sym.as_ref() == REQUIRE
+                            && prop.sym.as_ref() == RESOLVE
+                        {
+                            if let Expr::Lit(Lit::Str(s)) = &*arg.expr {
+                                return Some(&s.value);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    None
+}
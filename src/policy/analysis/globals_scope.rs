@@ -23,6 +23,7 @@ use indexmap::IndexSet;
 use crate::policy::analysis::{
     flatten, join_keys,
     scope_builder::{Scope, ScopeBuilder, WordOrPath},
+    suppression::{Rule, Suppressed, Suppressions},
 };
 
 // SEE: https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects
@@ -201,8 +202,14 @@ impl GlobalAnalysis {
         let mut builtins = std::mem::take(&mut self.builder.builtins);
         for builtin in candidates {
             if !builtin.matched {
+                let span = builtin.span;
                 let word_lists = builtin.word_lists();
                 for words in word_lists {
+                    self.builder
+                        .builtin_locations
+                        .entry(words.clone())
+                        .or_insert(Default::default())
+                        .push(span);
                     builtins.insert(words);
                 }
             }
@@ -210,6 +217,36 @@ impl GlobalAnalysis {
         builtins
     }
 
+    /// Remove computed globals and builtins that are suppressed
+    /// by inline comment directives.
+    ///
+    /// Must be called after computing the globals and builtins.
+    pub fn suppress(
+        &self,
+        suppressions: &Suppressions,
+        globals: IndexSet<Vec<JsWord>>,
+        builtins: IndexSet<Vec<JsWord>>,
+    ) -> (
+        IndexSet<Vec<JsWord>>,
+        IndexSet<Vec<JsWord>>,
+        Vec<Suppressed>,
+    ) {
+        let mut suppressed = Vec::new();
+        let globals = suppressions.filter(
+            globals,
+            &self.builder.locations,
+            Rule::Global,
+            &mut suppressed,
+        );
+        let builtins = suppressions.filter(
+            builtins,
+            &self.builder.builtin_locations,
+            Rule::Builtin,
+            &mut suppressed,
+        );
+        (globals, builtins, suppressed)
+    }
+
     /// Compute the global variables.
     pub fn compute_globals(&mut self) -> IndexSet<Vec<JsWord>> {
         let mut global_symbols: IndexSet<Vec<JsWord>> = Default::default();
@@ -8,6 +8,7 @@ pub mod dynamic_import;
 pub mod globals_scope;
 pub mod member_expr;
 pub mod scope_builder;
+pub mod suppression;
 
 /// Join the keys of a set into a single dot-delimited word.
 pub fn join_keys(set: IndexSet<Vec<JsWord>>) -> IndexSet<JsWord> {
@@ -7,9 +7,10 @@ use std::cell::RefCell;
 use std::rc::Rc;
 
 use swc_atoms::JsWord;
+use swc_common::{Span, Spanned};
 use swc_ecma_ast::*;
 
-use indexmap::IndexSet;
+use indexmap::{IndexMap, IndexSet};
 
 use crate::{
     helpers::{is_module_exports, pattern_words, var_symbol_words},
@@ -46,6 +47,8 @@ pub struct Builtin {
     pub(crate) source: JsWord,
     pub(crate) locals: Vec<Local>,
     pub(crate) matched: bool,
+    // Location of the import or call to `require()`.
+    pub(crate) span: Span,
 }
 
 impl Builtin {
@@ -220,8 +223,14 @@ pub struct ScopeBuilder {
     pub candidates: Vec<Builtin>,
     /// List of symbols that reference a builtin candidate.
     pub builtins: IndexSet<Vec<JsWord>>,
+    /// Locations of the symbol references.
+    pub locations: IndexMap<Vec<JsWord>, Vec<Span>>,
+    /// Locations of the references to builtins.
+    pub builtin_locations: IndexMap<Vec<JsWord>, Vec<Span>>,
     /// Whether to ignore the `global` keyword exposed by node.
     ignore_node_global: bool,
+    /// Span of the statement or expression being visited.
+    span: Span,
 }
 
 impl ScopeBuilder {
@@ -230,7 +239,10 @@ impl ScopeBuilder {
         Self {
             candidates: Default::default(),
             builtins: Default::default(),
+            locations: Default::default(),
+            builtin_locations: Default::default(),
             ignore_node_global,
+            span: Default::default(),
         }
     }
 
@@ -242,6 +254,7 @@ impl ScopeBuilder {
                 source: n.src.value.clone(),
                 locals: Default::default(),
                 matched: false,
+                span: n.span,
             };
             for spec in n.specifiers.iter() {
                 let local = match spec {
@@ -290,6 +303,10 @@ impl ScopeBuilder {
 
     #[inline(always)]
     fn insert_builtin(&mut self, words_key: Vec<JsWord>) {
+        self.builtin_locations
+            .entry(words_key.clone())
+            .or_insert(Default::default())
+            .push(self.span);
         self.builtins.insert(words_key);
     }
 
@@ -310,6 +327,7 @@ impl ScopeBuilder {
         scope: &mut Scope,
         locals: Option<IndexSet<JsWord>>,
     ) {
+        let parent_span = std::mem::replace(&mut self.span, n.span());
         match n {
             Stmt::Decl(decl) => {
                 match decl {
@@ -490,9 +508,11 @@ impl ScopeBuilder {
             Stmt::Expr(n) => self.visit_expr(&*n.expr, scope),
             _ => {}
         }
+        self.span = parent_span;
     }
 
     fn visit_expr(&mut self, n: &Expr, scope: &mut Scope) {
+        let parent_span = std::mem::replace(&mut self.span, n.span());
         match n {
             Expr::Ident(n) => {
                 self.insert_ident(n.sym.clone(), scope, None);
@@ -669,6 +689,7 @@ impl ScopeBuilder {
                             source: dynamic_call.arg.clone(),
                             locals: Default::default(),
                             matched: false,
+                            span: self.span,
                         };
 
                         // Assigning to module exports is a re-export so
@@ -739,6 +760,7 @@ impl ScopeBuilder {
                                 source: dynamic_call.arg.clone(),
                                 locals: Default::default(),
                                 matched: false,
+                                span: self.span,
                             };
                             if let Expr::Ident(id) = &*member.prop {
                                 builtin.locals =
@@ -840,6 +862,7 @@ impl ScopeBuilder {
             }
             _ => {}
         }
+        self.span = parent_span;
     }
 
     fn visit_class(
@@ -1042,6 +1065,7 @@ impl ScopeBuilder {
                         source: dynamic_call.arg.clone(),
                         locals: Default::default(),
                         matched: false,
+                        span: self.span,
                     };
                     builtin.locals = match &n.name {
                         // Looks like a default require statement
@@ -1223,7 +1247,7 @@ impl ScopeBuilder {
 
     #[inline(always)]
     fn insert_ident(
-        &self,
+        &mut self,
         mut sym: JsWord,
         scope: &mut Scope,
         mut path: Option<Vec<JsWord>>,
@@ -1256,6 +1280,10 @@ impl ScopeBuilder {
             }
         };
 
+        self.locations
+            .entry((&word_or_path).into())
+            .or_insert(Default::default())
+            .push(self.span);
         scope.idents.insert(word_or_path);
     }
 }
@@ -0,0 +1,298 @@
+//! Inline suppression directives declared in comments.
+//!
+//! A directive suppresses globals, builtins or lint rules for a single
+//! line of a module:
+//!
+//! ```javascript
+//! // basalt-ignore-next-line global:window
+//! window.location.reload();
+//!
+//! const fs = require('fs'); /* basalt-ignore builtin:fs */
+//! ```
+//!
+//! The `basalt-ignore-next-line` form applies to the line following the
+//! comment and the `basalt-ignore` form applies to the line the comment
+//! ends on.
+//!
+//! Rules are delimited by whitespace or commas; rules prefixed with
+//! `global:` or `builtin:` match a symbol path and all of the deeper paths
+//! beneath it so `global:process` also suppresses `process.env.FOO`. Any
+//! other rule is treated as the name of a lint rule. A directive without
+//! any rules suppresses everything on the line.
+//!
+//! A symbol is only suppressed when every location that references
+//! the symbol is covered by a directive.
+//!
+use std::fmt;
+
+use indexmap::{IndexMap, IndexSet};
+use serde::{Deserialize, Serialize};
+
+use swc_atoms::JsWord;
+use swc_common::{
+    comments::{Comment, SingleThreadedComments},
+    BytePos, SourceMap, Span,
+};
+
+use super::join_words;
+
+const IGNORE: &str = "basalt-ignore";
+const IGNORE_NEXT_LINE: &str = "basalt-ignore-next-line";
+const GLOBAL_PREFIX: &str = "global:";
+const BUILTIN_PREFIX: &str = "builtin:";
+
+static ALL: Rule = Rule::All;
+
+/// Rule that a directive applies to.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Rule {
+    /// Suppress everything.
+    All,
+    /// Suppress a global symbol path.
+    Global(String),
+    /// Suppress a builtin module path.
+    Builtin(String),
+    /// Suppress a lint rule.
+    Lint(String),
+}
+
+impl Rule {
+    fn parse(token: &str) -> Self {
+        if let Some(name) = token.strip_prefix(GLOBAL_PREFIX) {
+            Rule::Global(name.to_string())
+        } else if let Some(name) = token.strip_prefix(BUILTIN_PREFIX) {
+            Rule::Builtin(name.to_string())
+        } else {
+            Rule::Lint(token.to_string())
+        }
+    }
+
+    /// Determine if this rule matches another rule.
+    ///
+    /// Symbol paths match when the path is equal or is a
+    /// deeper path of the rule.
+    fn matches(&self, other: &Rule) -> bool {
+        match (self, other) {
+            (Rule::All, _) => true,
+            (Rule::Global(rule), Rule::Global(path))
+            | (Rule::Builtin(rule), Rule::Builtin(path)) => {
+                is_path_match(rule, path)
+            }
+            (Rule::Lint(rule), Rule::Lint(name)) => rule == name,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rule::All => write!(f, "*"),
+            Rule::Global(name) => write!(f, "{}{}", GLOBAL_PREFIX, name),
+            Rule::Builtin(name) => write!(f, "{}{}", BUILTIN_PREFIX, name),
+            Rule::Lint(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl Serialize for Rule {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Rule {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let token = String::deserialize(deserializer)?;
+        if token == "*" {
+            Ok(Rule::All)
+        } else {
+            Ok(Rule::parse(&token))
+        }
+    }
+}
+
+fn is_path_match(rule: &str, path: &str) -> bool {
+    path == rule
+        || (path.starts_with(rule) && path[rule.len()..].starts_with('.'))
+}
+
+/// Suppression directive that applies to a single line.
+#[derive(Debug, Clone)]
+pub struct Directive {
+    /// Rules for the directive.
+    pub rules: Vec<Rule>,
+    /// The line number (one-based) that the directive applies to.
+    pub line: usize,
+    lo: BytePos,
+    hi: BytePos,
+}
+
+impl Directive {
+    fn contains(&self, span: &Span) -> bool {
+        span.lo >= self.lo && span.lo < self.hi
+    }
+
+    fn find(&self, rule: &Rule) -> Option<&Rule> {
+        if self.rules.is_empty() {
+            return Some(&ALL);
+        }
+        self.rules.iter().find(|r| r.matches(rule))
+    }
+}
+
+/// Record of a suppression that was applied.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Suppressed {
+    /// The rule of the directive that applied.
+    pub rule: Rule,
+    /// The symbol path or lint rule that was suppressed.
+    pub symbol: String,
+    /// The line numbers (one-based) of the suppressed locations.
+    pub lines: Vec<usize>,
+}
+
+/// Collection of suppression directives for a module.
+#[derive(Debug, Clone, Default)]
+pub struct Suppressions {
+    directives: Vec<Directive>,
+}
+
+impl Suppressions {
+    /// Parse suppression directives from the comments for a module.
+    pub fn parse(
+        comments: &SingleThreadedComments,
+        source_map: &SourceMap,
+    ) -> Self {
+        let mut directives = Vec::new();
+        let (leading, trailing) = comments.borrow_all();
+        for comment in leading.values().chain(trailing.values()).flatten() {
+            if let Some(directive) = parse_directive(comment, source_map) {
+                directives.push(directive);
+            }
+        }
+        directives.sort_by_key(|d| d.lo);
+        Self { directives }
+    }
+
+    /// Determine if there are no directives.
+    pub fn is_empty(&self) -> bool {
+        self.directives.is_empty()
+    }
+
+    /// Find the rule of a directive that suppresses a rule at a location.
+    pub fn find(&self, rule: &Rule, span: &Span) -> Option<&Rule> {
+        self.directives
+            .iter()
+            .filter(|d| d.contains(span))
+            .find_map(|d| d.find(rule))
+    }
+
+    /// Find the directive line for a location.
+    fn line(&self, span: &Span) -> Option<usize> {
+        self.directives
+            .iter()
+            .find(|d| d.contains(span))
+            .map(|d| d.line)
+    }
+
+    /// Remove symbol paths where every location is suppressed.
+    ///
+    /// The factory function converts a joined symbol path to the
+    /// rule to match against.
+    pub fn filter<F>(
+        &self,
+        paths: IndexSet<Vec<JsWord>>,
+        locations: &IndexMap<Vec<JsWord>, Vec<Span>>,
+        factory: F,
+        suppressed: &mut Vec<Suppressed>,
+    ) -> IndexSet<Vec<JsWord>>
+    where
+        F: Fn(String) -> Rule,
+    {
+        if self.is_empty() {
+            return paths;
+        }
+
+        paths
+            .into_iter()
+            .filter(|words| {
+                let spans = match locations.get(words) {
+                    Some(spans) if !spans.is_empty() => spans,
+                    _ => return true,
+                };
+
+                let symbol = join_words(words).as_ref().to_string();
+                let rule = factory(symbol.clone());
+                let mut matched = None;
+                for span in spans {
+                    match self.find(&rule, span) {
+                        Some(directive_rule) => {
+                            matched = Some(directive_rule.clone())
+                        }
+                        None => return true,
+                    }
+                }
+
+                if let Some(rule) = matched {
+                    let mut lines: Vec<usize> =
+                        spans.iter().filter_map(|s| self.line(s)).collect();
+                    lines.sort();
+                    lines.dedup();
+                    suppressed.push(Suppressed {
+                        rule,
+                        symbol,
+                        lines,
+                    });
+                }
+                false
+            })
+            .collect()
+    }
+}
+
+fn parse_directive(
+    comment: &Comment,
+    source_map: &SourceMap,
+) -> Option<Directive> {
+    let text = comment.text.trim();
+    let (rest, next_line) =
+        if let Some(rest) = text.strip_prefix(IGNORE_NEXT_LINE) {
+            (rest, true)
+        } else if let Some(rest) = text.strip_prefix(IGNORE) {
+            (rest, false)
+        } else {
+            return None;
+        };
+
+    // Guard against words that share the prefix, eg: `basalt-ignored`
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let rules = rest
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|s| !s.is_empty())
+        .map(Rule::parse)
+        .collect::<Vec<_>>();
+
+    let loc = source_map.lookup_char_pos(comment.span.hi);
+    let line_index = if next_line { loc.line } else { loc.line - 1 };
+    if line_index >= loc.file.count_lines() {
+        return None;
+    }
+    let (lo, hi) = loc.file.line_bounds(line_index);
+
+    Some(Directive {
+        rules,
+        line: line_index + 1,
+        lo,
+        hi,
+    })
+}
@@ -1,29 +1,43 @@
 //! Build a package policy.
 
-use std::collections::{BTreeMap, HashMap, HashSet};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use anyhow::{bail, Result};
+use dashmap::DashMap;
 use indexmap::IndexSet;
+use serde::{Deserialize, Serialize};
 
 use swc_atoms::JsWord;
-use swc_common::{FileName, SourceMap};
-use swc_ecma_ast::TargetEnv;
-use swc_ecma_loader::{resolve::Resolve, resolvers::node::NodeModulesResolver};
+use swc_common::FileName;
+use swc_ecma_ast::Module;
 use swc_ecma_visit::VisitWith;
 
 use rayon::prelude::*;
 
-use super::{PackagePolicy, Policy, PolicyAccess};
+use super::{
+    debug::{
+        ModuleDynamicImport, ModuleSuppression, PackageDebug, PolicyDebug,
+    },
+    PackagePolicy, Policy, PolicyAccess,
+};
 use crate::{
-    helpers::normalize_specifier,
+    cache::Cache,
+    lint::DYNAMIC_IMPORT,
     module::{
-        base::module_base_directory,
         dependencies::is_dependent_module,
-        node::{cached_modules, parse_file, VisitedDependency, VisitedModule},
+        graph::ModuleGraph,
+        kind::declared_kind,
+        node::{ModuleNode, VisitedDependency, VisitedModule},
+    },
+    policy::analysis::{
+        dynamic_import::DynamicImport,
+        flatten,
+        globals_scope::GlobalAnalysis,
+        join_words,
+        suppression::{Rule, Suppressed},
     },
-    policy::analysis::{flatten, globals_scope::GlobalAnalysis, join_words},
 };
 
 /// Generate a policy.
@@ -33,46 +47,120 @@ use crate::{
 /// to convert a list of all modules into a collection of packages.
 pub struct PolicyBuilder {
     entry: PathBuf,
-    resolver: Box<dyn Resolve>,
-    source_map: Arc<SourceMap>,
-    /// Package buckets used the module specifier and the base path
+    graph: ModuleGraph,
+    /// Package buckets used the package name and the base path
     /// for the package as the key and map to all the modules inside
     /// the base path.
     package_buckets: HashMap<(String, PathBuf), HashSet<PathBuf>>,
 
+    /// Versions declared by the packages with the same name.
+    package_versions: HashMap<String, BTreeSet<String>>,
+
     /// Package buckets after grouping multiple versions of the same package.
     package_groups: HashMap<String, HashSet<PathBuf>>,
 
     /// Cumulative analysis for a package by merging the analysis for
     /// each module in the package.
     package_analysis: Policy,
+
+    /// Debug information for each package.
+    package_debug: PolicyDebug,
+
+    /// Base directory of the root package.
+    root: Option<PathBuf>,
+
+    /// Entry point that determines the root package when it is
+    /// not the package for the entry point.
+    root_entry: Option<PathBuf>,
+
+    /// Analysis for each module retained between builds so that
+    /// only invalidated modules are analyzed again.
+    module_analysis: DashMap<PathBuf, Arc<ModuleAnalysis>>,
 }
 
 impl PolicyBuilder {
     /// Create a package builder.
     pub fn new(entry: PathBuf) -> Self {
+        Self::with_graph(entry, ModuleGraph::new())
+    }
+
+    /// Create a package builder that loads modules into a graph.
+    pub fn with_graph(entry: PathBuf, graph: ModuleGraph) -> Self {
         Self {
             entry,
-            resolver: Box::new(NodeModulesResolver::new(
-                TargetEnv::Node,
-                Default::default(),
-            )),
-            source_map: Arc::new(Default::default()),
+            graph,
             package_buckets: Default::default(),
+            package_versions: Default::default(),
             package_groups: Default::default(),
             package_analysis: Default::default(),
+            package_debug: Default::default(),
+            root: None,
+            root_entry: None,
+            module_analysis: Default::default(),
         }
     }
 
+    /// The module graph for the entry point.
+    pub fn graph(&self) -> &ModuleGraph {
+        &self.graph
+    }
+
+    /// Invalidate the modules for files that have changed so that the
+    /// policy can be generated again by calling `load()` and `analyze()`.
+    pub fn invalidate(mut self, files: &HashSet<PathBuf>) -> Self {
+        self.graph.invalidate(files);
+        for file in files {
+            self.module_analysis.remove(file);
+        }
+        self.reset();
+        self
+    }
+
+    /// Change the entry point for the next build.
+    ///
+    /// The analysis for each module is retained so modules shared
+    /// with a previous entry point are not analyzed again.
+    pub fn entry(mut self, entry: PathBuf) -> Self {
+        self.entry = entry;
+        self.reset();
+        self
+    }
+
+    /// Use the package for another entry point as the root package.
+    ///
+    /// When several entry points are bundled the package for the
+    /// first entry point is the root package for every entry point.
+    pub fn root_entry(mut self, entry: PathBuf) -> Self {
+        self.root_entry = Some(entry);
+        self
+    }
+
+    /// Clear the packages and policy computed by the last build.
+    fn reset(&mut self) {
+        self.package_buckets.clear();
+        self.package_versions.clear();
+        self.package_groups.clear();
+        self.package_analysis = Default::default();
+        self.package_debug = Default::default();
+    }
+
     /// Load the entry point module and all dependencies grouping modules
     /// into dependent package buckets.
     pub fn load(mut self) -> Result<Self> {
-        let sm = Arc::clone(&self.source_map);
-        let module = parse_file(
-            &self.entry,
-            &self.resolver,
-            Arc::clone(&self.source_map),
-        )?;
+        self.root = self
+            .root_entry
+            .as_ref()
+            .unwrap_or(&self.entry)
+            .canonicalize()
+            .ok()
+            .and_then(|entry| self.graph.package(&entry))
+            .map(|package| package.base);
+
+        let graph = &self.graph;
+        let package_buckets = &mut self.package_buckets;
+        let package_versions = &mut self.package_versions;
+        let mut reachable: HashSet<PathBuf> = HashSet::new();
+        let module = graph.load(&self.entry)?;
 
         let node = match &*module {
             VisitedModule::Module(_, node) => Some(node),
@@ -80,33 +168,67 @@ impl PolicyBuilder {
             VisitedModule::Builtin(_) => None,
         };
 
+        // Modules in the root package are not assigned
+        // to a dependent package
+        let root_base = self.root.clone();
+
+        // An entry point outside the root package belongs to its package
+        if let Ok(entry) = self.entry.canonicalize() {
+            match graph.package(&entry) {
+                Some(package) if Some(&package.base) != root_base.as_ref() => {
+                    if let Some(version) = package.version {
+                        package_versions
+                            .entry(package.name.clone())
+                            .or_insert(Default::default())
+                            .insert(version);
+                    }
+                    package_buckets
+                        .entry((package.name, package.base))
+                        .or_insert(Default::default());
+                    reachable.insert(entry);
+                }
+                _ => {}
+            }
+        }
+
         let mut visitor = |dep: VisitedDependency| {
-            if is_dependent_module(&dep.spec) {
-                match dep.file_name {
-                    FileName::Real(path) => {
-                        if let Some(module_base) = module_base_directory(&path)
-                        {
-                            log::debug!(
-                                "Resolved {:#?} with {:#?}",
-                                &dep.spec,
-                                module_base.display()
-                            );
-                            self.package_buckets
-                                .entry((dep.spec.clone(), module_base))
-                                .or_insert(Default::default());
-                        } else {
-                            bail!("Failed to resolve module base for specifier {}", &dep.spec);
+            if let FileName::Real(path) = &dep.file_name {
+                reachable.insert(path.clone());
+                match graph.package(path) {
+                    Some(package)
+                        if Some(&package.base) != root_base.as_ref() =>
+                    {
+                        log::debug!(
+                            "Resolved {:#?} with {:#?} ({}@{})",
+                            &dep.spec,
+                            package.base.display(),
+                            &package.name,
+                            package.version.as_deref().unwrap_or("*"),
+                        );
+                        if let Some(version) = package.version {
+                            package_versions
+                                .entry(package.name.clone())
+                                .or_insert(Default::default())
+                                .insert(version);
                         }
+                        package_buckets
+                            .entry((package.name, package.base))
+                            .or_insert(Default::default());
+                    }
+                    None if is_dependent_module(&dep.spec) => {
+                        bail!(
+                            "Failed to resolve module base for specifier {}",
+                            &dep.spec
+                        );
                     }
                     _ => {}
                 }
             }
-
             Ok(())
         };
 
         if let Some(node) = node {
-            node.visit(sm, &mut visitor)?;
+            node.walk(graph, &mut visitor)?;
         }
 
         // Sort the module base keys as we need to find the deepest match
@@ -118,9 +240,12 @@ impl PolicyBuilder {
             .collect();
         base_keys.sort();
 
-        // Put the cached module paths in each package bucket.
-        for item in cached_modules().iter() {
-            let key = item.key();
+        // Put the loaded module paths in each package bucket, modules
+        // that are no longer reachable after invalidation are ignored.
+        for key in reachable.iter() {
+            if self.graph.get(key).is_none() {
+                continue;
+            }
             if let Some(module_base) =
                 base_keys.iter().rev().find(|p| key.starts_with(p))
             {
@@ -134,30 +259,10 @@ impl PolicyBuilder {
             }
         }
 
-        Ok(self.flatten()?.group()?)
+        Ok(self.group()?)
     }
 
-    /// Flatten package nested paths so that the modules are grouped
-    /// with the parent package.
-    fn flatten(mut self) -> Result<Self> {
-        let mut tmp: HashMap<(String, PathBuf), HashSet<PathBuf>> =
-            HashMap::new();
-        for ((spec, module_base), modules) in self.package_buckets.drain() {
-            let key = normalize_specifier(&spec);
-            let entry = tmp
-                .entry((key, module_base.clone()))
-                .or_insert(Default::default());
-            for p in modules {
-                entry.insert(p);
-            }
-        }
-
-        self.package_buckets = tmp;
-
-        Ok(self)
-    }
-
-    /// Merge packages with the same specifier.
+    /// Merge packages with the same name.
     ///
     /// The npm package manager allows multiple versions of the same package
     /// so we merge them into a single bucket with all of the modules so
@@ -182,13 +287,25 @@ impl PolicyBuilder {
         let analyzed: Vec<_> = groups
             .into_par_iter()
             .map(|(spec, modules)| {
-                let result = analyze_modules(&spec, modules);
+                let result = analyze_modules(
+                    &self.graph,
+                    &self.module_analysis,
+                    self.root.as_deref(),
+                    &spec,
+                    modules,
+                );
                 (spec, result)
             })
             .collect();
 
         for (spec, policy) in analyzed {
-            let analysis = policy?;
+            let (analysis, mut debug) = policy?;
+            if let Some(versions) = self.package_versions.get(&spec) {
+                debug.versions = versions.clone();
+            }
+            if !debug.is_empty() {
+                self.package_debug.resources.insert(spec.clone(), debug);
+            }
             if !analysis.is_empty() {
                 self.package_analysis.insert(spec, analysis);
             }
@@ -197,77 +314,249 @@ impl PolicyBuilder {
         Ok(self)
     }
 
+    /// Debug information collected during analysis.
+    pub fn debug(&self) -> &PolicyDebug {
+        &self.package_debug
+    }
+
+    /// The generated policy.
+    pub fn policy(&self) -> &Policy {
+        &self.package_analysis
+    }
+
     /// Generate a package policy file.
     pub fn finalize(self) -> Policy {
         self.package_analysis
     }
+
+    /// Generate a package policy file and return the module graph.
+    pub fn into_parts(self) -> (Policy, ModuleGraph) {
+        (self.package_analysis, self.graph)
+    }
+}
+
+/// Cache entry kind for module analysis.
+const ANALYSIS: &str = "analysis";
+
+/// Analysis for a single module.
+///
+/// Stored in the analysis cache keyed by the module path, contents
+/// and declared kind so that unchanged modules do not need to be
+/// parsed.
+#[derive(Default, Serialize, Deserialize)]
+struct ModuleAnalysis {
+    globals: Vec<Vec<String>>,
+    builtins: Vec<Vec<String>>,
+    suppressed: Vec<Suppressed>,
+    dynamic_imports: Vec<DynamicImport>,
+}
+
+impl ModuleAnalysis {
+    /// Compute the analysis for a parsed module.
+    fn new(node: &ModuleNode, module: &Module) -> Self {
+        // Compute globals
+        let mut globals_scope = GlobalAnalysis::new(Default::default());
+        module.visit_children_with(&mut globals_scope);
+        let globals = globals_scope.compute_globals();
+
+        // Compute builtins
+        let builtin = globals_scope.compute_builtins();
+
+        // Apply inline suppressions
+        let (globals, builtin, mut suppressed) =
+            globals_scope.suppress(&node.suppressions, globals, builtin);
+
+        // Dynamic imports that cannot be resolved statically
+        let mut dynamic_imports = Vec::new();
+        let rule = Rule::Lint(DYNAMIC_IMPORT.to_string());
+        for import in node.dynamic_imports.iter() {
+            if let Some(directive_rule) =
+                node.suppressions.find(&rule, &import.span)
+            {
+                suppressed.push(Suppressed {
+                    rule: directive_rule.clone(),
+                    symbol: DYNAMIC_IMPORT.to_string(),
+                    lines: vec![import.line],
+                });
+            } else {
+                dynamic_imports.push(import.clone());
+            }
+        }
+
+        Self {
+            globals: into_strings(globals),
+            builtins: into_strings(builtin),
+            suppressed,
+            dynamic_imports,
+        }
+    }
+
+    /// Load the analysis for a module from the analysis cache or
+    /// parse the module and compute the analysis.
+    fn load(
+        graph: &ModuleGraph,
+        file_name: &FileName,
+        node: &ModuleNode,
+    ) -> Result<Self> {
+        let cache = graph.analysis_cache();
+        let key = match (cache, file_name, &node.hash) {
+            (Some(_), FileName::Real(path), Some(hash)) => {
+                let declared = format!("{:?}", declared_kind(path));
+                Some(Cache::key(&[
+                    ANALYSIS.as_bytes(),
+                    path.to_string_lossy().as_bytes(),
+                    hash.as_bytes(),
+                    declared.as_bytes(),
+                ]))
+            }
+            _ => None,
+        };
+
+        let cache = cache.zip(key.as_ref());
+        if let Some((cache, key)) = cache {
+            if let Some(analysis) = cache.get(ANALYSIS, key) {
+                return Ok(analysis);
+            }
+        }
+
+        let reparsed = graph.reparse(file_name, node)?;
+        let node = reparsed.as_ref().unwrap_or(node);
+        let analysis = if let Some(module) = &node.module {
+            ModuleAnalysis::new(node, module)
+        } else {
+            Default::default()
+        };
+
+        if let Some((cache, key)) = cache {
+            cache.put(ANALYSIS, key, &analysis)?;
+        }
+        Ok(analysis)
+    }
+}
+
+fn into_strings(words: IndexSet<Vec<JsWord>>) -> Vec<Vec<String>> {
+    words
+        .into_iter()
+        .map(|words| words.iter().map(|w| w.as_ref().to_string()).collect())
+        .collect()
+}
+
+fn into_words(strings: &[Vec<String>]) -> IndexSet<Vec<JsWord>> {
+    strings
+        .iter()
+        .map(|words| words.iter().map(|w| JsWord::from(&w[..])).collect())
+        .collect()
 }
 
 /// Walk all the modules in a package and perform a cumulative analysis.
 fn analyze_modules(
+    graph: &ModuleGraph,
+    module_analysis: &DashMap<PathBuf, Arc<ModuleAnalysis>>,
+    root: Option<&Path>,
     spec: &str,
     modules: HashSet<PathBuf>,
-) -> Result<PackagePolicy> {
-    let cache = cached_modules();
-
+) -> Result<(PackagePolicy, PackageDebug)> {
     // Aggregated analysis data
     let mut analysis: PackagePolicy = Default::default();
+    let mut debug: PackageDebug = Default::default();
 
     let data: Vec<(
         IndexSet<Vec<JsWord>>,
         IndexSet<Vec<JsWord>>,
         IndexSet<String>,
+        PackageDebug,
     )> = modules
         .into_par_iter()
         .map(|module_key| {
-            let cached_module = cache.get(&module_key).unwrap();
-            let visited_module = cached_module.value();
-            if let VisitedModule::Module(_, node) = &**visited_module {
-                // Compute globals
-                let mut globals_scope = GlobalAnalysis::new(Default::default());
-                node.module.visit_children_with(&mut globals_scope);
-                let globals = globals_scope.compute_globals();
-
-                // Compute builtins
-                let builtin = globals_scope.compute_builtins();
-
-                // Compute dependent packages
-                let packages = if let Some(deps) = &node.dependencies {
-                    deps.iter()
-                        .filter_map(|dep| {
-                            let normalized =
-                                normalize_specifier(dep.specifier.as_ref());
-                            // Some packages such as @babel/runtime can end up with
-                            // themselves in the dependency list so we explicitly disallow this
-                            if spec != &normalized
-                                && is_dependent_module(dep.specifier.as_ref())
-                            {
-                                Some(normalized)
-                            } else {
-                                None
-                            }
-                        })
-                        .collect::<IndexSet<String>>()
-                } else {
-                    IndexSet::new()
+            let visited_module = graph.get(&module_key).unwrap();
+            if let VisitedModule::Module(file_name, node) = &*visited_module {
+                let mut module_debug: PackageDebug = Default::default();
+                let analysis = match module_analysis.get(&module_key) {
+                    Some(entry) => Arc::clone(entry.value()),
+                    None => {
+                        let analysis = Arc::new(ModuleAnalysis::load(
+                            graph, file_name, node,
+                        )?);
+                        module_analysis
+                            .insert(module_key.clone(), Arc::clone(&analysis));
+                        analysis
+                    }
                 };
 
-                return (globals, builtin, packages);
+                for import in analysis.dynamic_imports.iter() {
+                    log::debug!(
+                        "Found {} in {} (line {})",
+                        import,
+                        module_key.display(),
+                        import.line,
+                    );
+                    module_debug.unresolvable_dynamic_imports.push(
+                        ModuleDynamicImport {
+                            file: module_key.clone(),
+                            import: import.clone(),
+                        },
+                    );
+                }
+
+                for suppressed in analysis.suppressed.iter() {
+                    log::debug!(
+                        "Suppressed {} in {} (line {:?})",
+                        &suppressed.symbol,
+                        module_key.display(),
+                        &suppressed.lines,
+                    );
+                    module_debug.suppressions.push(ModuleSuppression {
+                        file: module_key.clone(),
+                        suppressed: suppressed.clone(),
+                    });
+                }
+
+                // Compute dependent packages from the package that
+                // each resolved dependency belongs to
+                let packages = node
+                    .resolved
+                    .iter()
+                    .filter_map(|(_, file_name)| match file_name {
+                        FileName::Real(path) => graph.package(path),
+                        _ => None,
+                    })
+                    // Some packages such as @babel/runtime can end up with
+                    // themselves in the dependency list so we explicitly disallow this
+                    .filter(|package| {
+                        package.name != spec
+                            && Some(package.base.as_path()) != root
+                    })
+                    .map(|package| package.name)
+                    .collect::<IndexSet<String>>();
+
+                return Ok((
+                    into_words(&analysis.globals),
+                    into_words(&analysis.builtins),
+                    packages,
+                    module_debug,
+                ));
             }
-            (IndexSet::new(), IndexSet::new(), IndexSet::new())
+            Ok((
+                IndexSet::new(),
+                IndexSet::new(),
+                IndexSet::new(),
+                Default::default(),
+            ))
         })
-        .collect();
+        .collect::<Result<Vec<_>>>()?;
 
     // Group the computations for each package
     let mut pkg_globals = IndexSet::new();
     let mut pkg_builtin = IndexSet::new();
     let mut pkg_packages = IndexSet::new();
 
-    for (globals, builtin, packages) in data {
+    for (globals, builtin, packages, mut module_debug) in data {
         pkg_globals = pkg_globals.union(&globals).cloned().collect();
         pkg_builtin = pkg_builtin.union(&builtin).cloned().collect();
         pkg_packages = pkg_packages.union(&packages).cloned().collect();
+        debug.append(&mut module_debug);
     }
+    debug.sort();
 
     // Flatten globals and builtins
     pkg_globals = flatten(pkg_globals);
@@ -293,5 +582,5 @@ fn analyze_modules(
     analysis.builtin.append(&mut builtin_map);
     analysis.packages.append(&mut packages_map);
 
-    Ok(analysis)
+    Ok((analysis, debug))
 }
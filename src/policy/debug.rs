@@ -0,0 +1,76 @@
+//! Debug information collected whilst generating a policy.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use super::analysis::{dynamic_import::DynamicImport, suppression::Suppressed};
+
+/// Debug information for all the packages in a policy.
+#[derive(Serialize, Default, Debug)]
+pub struct PolicyDebug {
+    /// Debug information for each package.
+    pub resources: BTreeMap<String, PackageDebug>,
+}
+
+/// Debug information for a single package.
+#[derive(Serialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageDebug {
+    /// Versions of the package declared in package.json.
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub versions: BTreeSet<String>,
+    /// Inline suppressions applied to modules in the package.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub suppressions: Vec<ModuleSuppression>,
+    /// Calls to `require()` or `import()` in the package that
+    /// cannot be resolved statically.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unresolvable_dynamic_imports: Vec<ModuleDynamicImport>,
+}
+
+impl PackageDebug {
+    /// Determine if there is no debug information for the package.
+    pub fn is_empty(&self) -> bool {
+        self.versions.is_empty()
+            && self.suppressions.is_empty()
+            && self.unresolvable_dynamic_imports.is_empty()
+    }
+
+    /// Move the debug information from other into this package.
+    pub fn append(&mut self, other: &mut PackageDebug) {
+        self.versions.append(&mut other.versions);
+        self.suppressions.append(&mut other.suppressions);
+        self.unresolvable_dynamic_imports
+            .append(&mut other.unresolvable_dynamic_imports);
+    }
+
+    /// Sort the debug information by file.
+    pub fn sort(&mut self) {
+        self.suppressions.sort_by(|a, b| a.file.cmp(&b.file));
+        self.unresolvable_dynamic_imports.sort_by(|a, b| {
+            a.file.cmp(&b.file).then(a.import.line.cmp(&b.import.line))
+        });
+    }
+}
+
+/// Suppression that was applied to a module.
+#[derive(Serialize, Debug)]
+pub struct ModuleSuppression {
+    /// The module file.
+    pub file: PathBuf,
+    /// The applied suppression.
+    #[serde(flatten)]
+    pub suppressed: Suppressed,
+}
+
+/// Dynamic import in a module.
+#[derive(Serialize, Debug)]
+pub struct ModuleDynamicImport {
+    /// The module file.
+    pub file: PathBuf,
+    /// The dynamic import.
+    #[serde(flatten)]
+    pub import: DynamicImport,
+}
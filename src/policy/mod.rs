@@ -9,6 +9,7 @@ use std::fmt;
 
 pub mod analysis;
 pub mod builder;
+pub mod debug;
 
 /// Trait for the merge operation.
 pub trait Merge {
@@ -152,6 +153,11 @@ impl PolicyGroup {
         self.map.insert(key.as_ref().into(), value);
     }
 
+    /// Iterate the keys in this group.
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.map.keys()
+    }
+
     /// Append a map of packages to this group.
     pub fn append(&mut self, other: &mut BTreeMap<String, PolicyAccess>) {
         self.map.append(other);
@@ -1,13 +1,12 @@
 //! Utility to print the module graph as a tree.
 use std::path::Path;
-use std::sync::Arc;
 
 use anyhow::Result;
 
-use crate::module::node::{parse_file, VisitedDependency, VisitedModule};
-
-use swc_common::SourceMap;
-use swc_ecma_loader::{resolve::Resolve, resolvers::node::NodeModulesResolver};
+use crate::module::{
+    graph::ModuleGraph,
+    node::{VisitedDependency, VisitedModule},
+};
 
 const TREE_BAR: &str = "│";
 const TREE_BRANCH: &str = "├──";
@@ -32,14 +31,11 @@ impl Printer {
     /// List module imports for an entry point.
     pub fn print<P: AsRef<Path>>(
         &self,
+        graph: &ModuleGraph,
         file: P,
         options: &PrintOptions,
     ) -> Result<()> {
-        let resolver: Box<dyn Resolve> =
-            Box::new(NodeModulesResolver::default());
-        let source_map: Arc<SourceMap> = Arc::new(Default::default());
-        let module =
-            parse_file(file.as_ref(), &resolver, Arc::clone(&source_map))?;
+        let module = graph.load(file.as_ref())?;
         let node = match &*module {
             VisitedModule::Module(_, node) => Some(node),
             VisitedModule::Json(_, node) => Some(node),
@@ -68,6 +64,10 @@ impl Printer {
                 print!(" {}", dep.file_name);
             }
 
+            if dep.optional {
+                print!(" (optional)");
+            }
+
             if let Some(cycle) = dep.cycles {
                 print!(" (∞ -> {})", cycle);
             }
@@ -78,7 +78,7 @@ impl Printer {
         };
 
         if let Some(node) = node {
-            node.visit(source_map, &mut visitor)?;
+            node.visit(graph, &mut visitor)?;
         }
 
         Ok(())
@@ -5,7 +5,7 @@ use indexmap::{IndexMap, IndexSet};
 
 use swc_ecma_ast::Module;
 
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Serialize, Serializer};
 
 /// Type for live exports.
 pub type LiveExport<'a> = (&'a str, bool);
@@ -77,7 +77,7 @@ pub struct StaticModuleRecord<'a> {
 }
 
 /// Owned static module record meta data.
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct StaticModuleRecordMeta {
     /// All exports, eg: `export * from './foo.js';`
@@ -91,12 +91,16 @@ pub struct StaticModuleRecordMeta {
 }
 
 /// Meta data and transformed program combined.
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct StaticModuleRecordProgram {
     /// The meta data.
     pub meta: StaticModuleRecordMeta,
     /// The module transformed to a program functor.
     pub program: String,
+    /// Source map for the program functor.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_map: Option<String>,
 }
 
 impl Into<StaticModuleRecordMeta> for StaticModuleRecord<'_> {
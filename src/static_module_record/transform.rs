@@ -4,31 +4,31 @@ use std::sync::Arc;
 
 use indexmap::IndexMap;
 
-use swc::{
-    config::{JscTarget, SourceMapsConfig},
-    TransformOutput,
-};
+use swc::{config::SourceMapsConfig, TransformOutput};
 use swc_common::{
     errors::{emitter::ColorConfig, Handler},
-    FileName, SourceMap, DUMMY_SP,
+    FileName, SourceMap, Span, DUMMY_SP,
 };
 
 use swc_atoms::JsWord;
 use swc_ecma_ast::*;
-use swc_ecma_parser::{lexer::Lexer, Parser, StringInput, Syntax};
 use swc_ecma_visit::{Node, Visit, VisitWith};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 
 use super::{
     ImportName, Parser as StaticModuleRecordParser, StaticModuleRecord,
     StaticModuleRecordMeta,
 };
 
-use crate::{helpers::var_symbol_names, swc_utils};
+use crate::{
+    helpers::{var_symbol_names, EXPORTS, MODULE, REQUIRE},
+    module::kind::{module_kind, ModuleKind, ModuleSyntax},
+    swc_utils,
+};
 
-const HIDDEN_PREFIX: &str = "$h\u{200d}_";
-const HIDDEN_CONST_VAR_PREFIX: &str = "$c\u{200d}_";
+pub(crate) const HIDDEN_PREFIX: &str = "$h\u{200d}_";
+pub(crate) const HIDDEN_CONST_VAR_PREFIX: &str = "$c\u{200d}_";
 const IMPORTS: &str = "imports";
 const LIVE_VAR: &str = "liveVar";
 const ONCE_VAR: &str = "onceVar";
@@ -89,6 +89,8 @@ pub struct ParseOutput<'a> {
 }
 
 /// Transform source to codegen output.
+///
+/// The output includes a source map for the functor program.
 pub fn transform(
     source: TransformSource,
     source_map: Arc<SourceMap>,
@@ -100,7 +102,9 @@ pub fn transform(
         source_map,
         None,
         None,
-        SourceMapsConfig::Bool(false),
+        false,
+        SourceMapsConfig::Bool(true),
+        false,
     )?;
 
     Ok((meta, result))
@@ -118,6 +122,11 @@ pub fn transform_program(
         Some(source_map.clone()),
     );
 
+    let file = match &source {
+        TransformSource::File(path) => Some(path.clone()),
+        _ => None,
+    };
+
     let fm = match source {
         TransformSource::File(path) => source_map.load_file(&path)?,
         TransformSource::Str { content, file_name } => source_map
@@ -127,22 +136,24 @@ pub fn transform_program(
             ),
     };
 
-    let lexer = Lexer::new(
-        Syntax::Es(Default::default()),
-        JscTarget::Es2020,
-        StringInput::from(&*fm),
-        None,
-    );
-
-    let mut parser = Parser::new_from(lexer);
+    let mut parser = swc_utils::get_parser(&*fm, None);
     for e in parser.take_errors() {
         e.into_diagnostic(&handler).emit();
     }
 
-    let module = parser
-        .parse_module()
-        .map_err(|e| e.into_diagnostic(&handler).emit())
-        .expect("failed to parse module");
+    let module = parser.parse_module().map_err(|e| {
+        swc_utils::parse_error(&handler, &fm.name, &source_map, e)
+    })?;
+    let module = swc_utils::strip_types(&fm.name, module);
+
+    // Scripts without any module syntax are transformed
+    // but CommonJS modules are not static module records,
+    // TypeScript modules compiled to CommonJS are mixed
+    if module_kind(file.as_deref(), &module)? == ModuleKind::Cjs
+        && !ModuleSyntax::detect(&module).is_script()
+    {
+        bail!("module {} is a CommonJS module", fm.name);
+    }
 
     Ok(transform_module_script(&module)?)
 }
@@ -173,6 +184,23 @@ pub fn transform_module_function(
     Ok((meta.into(), func))
 }
 
+/// Transform a module that mixes ES module syntax with CommonJS
+/// to a function.
+///
+/// The function receives the `require`, `module` and `exports`
+/// bindings alongside the static module record functor arguments.
+pub fn transform_mixed_module_function(
+    module: &Module,
+) -> Result<(StaticModuleRecordMeta, Function)> {
+    let mut parser = StaticModuleRecordParser::new();
+    let meta = parser.parse(module)?;
+    let generator = Generator::new(&meta).commonjs(true);
+    let func = generator
+        .function()
+        .context("failed to generate transformed function")?;
+    Ok((meta.into(), func))
+}
+
 struct Visitor<'a> {
     meta: &'a StaticModuleRecord<'a>,
     body: &'a mut Vec<Stmt>,
@@ -182,6 +210,7 @@ fn call_stmt(
     prop_target: JsWord,
     prop_name: &str,
     mut arg: Option<JsWord>,
+    span: Span,
 ) -> Stmt {
     let args = if let Some(arg) = arg.take() {
         vec![ExprOrSpread {
@@ -197,9 +226,9 @@ fn call_stmt(
     };
 
     Stmt::Expr(ExprStmt {
-        span: DUMMY_SP,
+        span,
         expr: Box::new(Expr::Call(CallExpr {
-            span: DUMMY_SP,
+            span,
             callee: ExprOrSuper::Expr(Box::new(Expr::Member(MemberExpr {
                 span: DUMMY_SP,
                 obj: ExprOrSuper::Expr(Box::new(Expr::Ident(Ident {
@@ -224,9 +253,10 @@ fn default_stmt(
     prop_target: JsWord,
     prop_arg: JsWord,
     value: Box<Expr>,
+    span: Span,
 ) -> (Stmt, Stmt) {
     let default_stmt = Stmt::Decl(Decl::Var(VarDecl {
-        span: DUMMY_SP,
+        span,
         // Default exports must be constant
         kind: VarDeclKind::Const,
         declare: false,
@@ -271,7 +301,7 @@ fn default_stmt(
 
     (
         default_stmt,
-        call_stmt(prop_target, "default", Some(prop_arg)),
+        call_stmt(prop_target, "default", Some(prop_arg), span),
     )
 }
 
@@ -364,6 +394,7 @@ impl<'a> Visit for Visitor<'a> {
                                     prop_target,
                                     export_name,
                                     Some(local_name.into()),
+                                    export.span,
                                 );
                                 self.body.push(call);
                             }
@@ -382,8 +413,12 @@ impl<'a> Visit for Visitor<'a> {
                         }
                         _ => panic!("Typescript interface declarations are not supported")
                     };
-                    let (default_stmt, call) =
-                        default_stmt(prop_target, prop_arg, value_expr);
+                    let (default_stmt, call) = default_stmt(
+                        prop_target,
+                        prop_arg,
+                        value_expr,
+                        export.span,
+                    );
                     self.body.push(default_stmt);
                     self.body.push(call);
                 }
@@ -394,8 +429,12 @@ impl<'a> Visit for Visitor<'a> {
                         let prop_target = prefix_hidden(ONCE);
                         let prop_arg = prefix_const(DEFAULT);
                         let value_expr = export.expr.clone();
-                        let (default_stmt, call) =
-                            default_stmt(prop_target, prop_arg, value_expr);
+                        let (default_stmt, call) = default_stmt(
+                            prop_target,
+                            prop_arg,
+                            value_expr,
+                            export.span,
+                        );
                         self.body.push(default_stmt);
                         self.body.push(call);
                     }
@@ -411,7 +450,7 @@ impl<'a> Visit for Visitor<'a> {
                                     if !decl_emitted {
                                         self.body.push(Stmt::Decl(Decl::Var(
                                             VarDecl {
-                                                span: DUMMY_SP,
+                                                span: var.span,
                                                 kind: var.kind.clone(),
                                                 declare: false,
                                                 decls: vec![decl.clone()],
@@ -426,6 +465,7 @@ impl<'a> Visit for Visitor<'a> {
                                         prop_target,
                                         name,
                                         Some(name.into()),
+                                        decl.span,
                                     );
                                     self.body.push(call);
                                 } else if self
@@ -438,11 +478,11 @@ impl<'a> Visit for Visitor<'a> {
                                     if !decl_emitted {
                                         self.body.push(Stmt::Decl(Decl::Var(
                                             VarDecl {
-                                                span: DUMMY_SP,
+                                                span: var.span,
                                                 kind: VarDeclKind::Let,
                                                 declare: false,
                                                 decls: vec![VarDeclarator {
-                                                    span: DUMMY_SP,
+                                                    span: decl.span,
                                                     name: Pat::Ident(
                                                         BindingIdent {
                                                             id: Ident {
@@ -466,7 +506,7 @@ impl<'a> Visit for Visitor<'a> {
                                     if self.meta.hoisted_refs.contains(name) {
                                         self.body.push(
                                             Stmt::Expr(ExprStmt {
-                                                span: DUMMY_SP,
+                                                span: decl.span,
                                                 expr: Box::new(Expr::Assign(AssignExpr {
                                                     span: DUMMY_SP,
                                                     op: AssignOp::Assign,
@@ -492,6 +532,7 @@ impl<'a> Visit for Visitor<'a> {
                                             prop_target,
                                             name,
                                             Some(prop_name),
+                                            decl.span,
                                         );
                                         self.body.push(call);
                                     }
@@ -507,7 +548,7 @@ impl<'a> Visit for Visitor<'a> {
 
                         // Output the function
                         self.body.push(Stmt::Expr(ExprStmt {
-                            span: DUMMY_SP,
+                            span: export.span,
                             expr: Box::new(Expr::Fn(FnExpr {
                                 ident: Some(ident),
                                 function: func.function.clone(),
@@ -524,6 +565,7 @@ impl<'a> Visit for Visitor<'a> {
                             prop_target,
                             name,
                             Some(JsWord::from(name)),
+                            export.span,
                         );
                         self.body.push(call);
                     }
@@ -543,18 +585,28 @@ impl<'a> Visit for Visitor<'a> {
 /// Generate a static module record functor program.
 pub struct Generator<'a> {
     meta: &'a StaticModuleRecord<'a>,
+    commonjs: bool,
 }
 
 impl<'a> Generator<'a> {
     /// Create a new generator.
     pub fn new(meta: &'a StaticModuleRecord<'a>) -> Self {
-        Generator { meta }
+        Generator {
+            meta,
+            commonjs: false,
+        }
+    }
+
+    /// Include the CommonJS bindings in the functor arguments.
+    pub fn commonjs(mut self, commonjs: bool) -> Self {
+        self.commonjs = commonjs;
+        self
     }
 
     /// Create the program as a function.
     pub fn function(&self) -> Result<Function> {
         Ok(Function {
-            span: DUMMY_SP,
+            span: self.meta.module.span,
             params: self
                 .params()
                 .into_iter()
@@ -586,7 +638,7 @@ impl<'a> Generator<'a> {
             expr: Box::new(Expr::Paren(ParenExpr {
                 span: DUMMY_SP,
                 expr: Box::new(Expr::Arrow(ArrowExpr {
-                    span: DUMMY_SP,
+                    span: self.meta.module.span,
                     params: self.params(),
                     body: BlockStmtOrExpr::BlockStmt(self.body()),
                     is_async: false,
@@ -629,6 +681,25 @@ impl<'a> Generator<'a> {
                         })),
                     }));
                 }
+                if self.commonjs {
+                    for name in &[REQUIRE, MODULE, EXPORTS] {
+                        out.push(ObjectPatProp::KeyValue(KeyValuePatProp {
+                            key: PropName::Ident(Ident {
+                                span: DUMMY_SP,
+                                sym: (*name).into(),
+                                optional: false,
+                            }),
+                            value: Box::new(Pat::Ident(BindingIdent {
+                                id: Ident {
+                                    span: DUMMY_SP,
+                                    sym: (*name).into(),
+                                    optional: false,
+                                },
+                                type_ann: None,
+                            })),
+                        }));
+                    }
+                }
                 out
             },
             optional: false,
@@ -697,7 +768,7 @@ impl<'a> Generator<'a> {
 
             // Set up the live export
             let prop_target = prefix_hidden(LIVE);
-            let call = call_stmt(prop_target, name, Some(target));
+            let call = call_stmt(prop_target, name, Some(target), DUMMY_SP);
             stmts.push(call);
         }
     }
@@ -706,7 +777,7 @@ impl<'a> Generator<'a> {
         for name in self.meta.hoisted_refs.iter() {
             // Set up the live export
             let prop_target = prefix_hidden(LIVE);
-            let call = call_stmt(prop_target, name, None);
+            let call = call_stmt(prop_target, name, None, DUMMY_SP);
             stmts.push(call);
         }
     }
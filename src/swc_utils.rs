@@ -6,18 +6,25 @@ use anyhow::Result;
 
 use swc::{
     config::{JscTarget, SourceMapsConfig},
+    ecmascript::transforms::{react::jsx, typescript::strip},
     Compiler, TransformOutput,
 };
 use swc_common::{
+    comments::{Comments, SingleThreadedComments},
     errors::{emitter::ColorConfig, Handler},
-    FileName, SourceFile, SourceMap,
+    FileName, Globals, SourceFile, SourceMap, Spanned, GLOBALS,
 };
-use swc_ecma_ast::Module;
+use swc_ecma_ast::{JSXElement, JSXFragment, Module};
 use swc_ecma_codegen::Node;
-use swc_ecma_parser::{lexer::Lexer, EsConfig, Parser, StringInput, Syntax};
+use swc_ecma_parser::{
+    error::Error as ParserError, lexer::Lexer, EsConfig, Parser, StringInput,
+    Syntax, TsConfig,
+};
 
 use swc::IdentCollector;
-use swc_ecma_visit::VisitWith;
+use swc_ecma_visit::{FoldWith, Node as VisitNode, Visit, VisitWith};
+
+use crate::error::ModuleError;
 
 pub(crate) fn get_handler(
     source_map: Option<Arc<SourceMap>>,
@@ -33,46 +40,145 @@ pub(crate) fn get_handler(
     (sm, handler)
 }
 
+/// File extensions for TypeScript modules.
+pub(crate) const TYPESCRIPT_EXTENSIONS: &[&str] = &["ts", "tsx", "mts", "cts"];
+
+/// Determine if a file is a TypeScript module by file extension.
+pub(crate) fn is_typescript(file_name: &FileName) -> bool {
+    match file_name {
+        FileName::Real(path) => path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| TYPESCRIPT_EXTENSIONS.contains(&e))
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// Get the syntax for a file using the file extension.
+fn get_syntax(file_name: &FileName) -> Syntax {
+    if is_typescript(file_name) {
+        let name = file_name.to_string();
+        Syntax::Typescript(TsConfig {
+            tsx: name.ends_with(".tsx"),
+            decorators: true,
+            dynamic_import: true,
+            dts: name.ends_with(".d.ts"),
+            ..Default::default()
+        })
+    } else {
+        Syntax::Es(EsConfig {
+            jsx: true,
+            dynamic_import: true,
+            ..Default::default()
+        })
+    }
+}
+
 pub(crate) fn get_parser<'a>(
     fm: &'a SourceFile,
+    comments: Option<&'a dyn Comments>,
 ) -> Parser<Lexer<'a, StringInput<'a>>> {
-    let es_config = EsConfig {
-        jsx: true,
-        dynamic_import: true,
-        ..Default::default()
-    };
-
     let lexer = Lexer::new(
-        Syntax::Es(es_config),
+        get_syntax(&fm.name),
         JscTarget::Es2020,
         StringInput::from(fm),
-        None,
+        comments,
     );
     Parser::new_from(lexer)
 }
 
+/// Emit a diagnostic for a fatal parser error and convert
+/// it to a module error.
+pub(crate) fn parse_error(
+    handler: &Handler,
+    file_name: &FileName,
+    source_map: &SourceMap,
+    e: ParserError,
+) -> anyhow::Error {
+    let error = ModuleError::parse(
+        file_name,
+        source_map,
+        e.span(),
+        e.kind().msg().to_string(),
+    );
+    e.into_diagnostic(handler).emit();
+    error.into()
+}
+
+/// Remove the types from a TypeScript module so that it can be
+/// analyzed and bundled in the same way as a JavaScript module.
+pub(crate) fn strip_types(file_name: &FileName, module: Module) -> Module {
+    if !is_typescript(file_name) {
+        return module;
+    }
+    GLOBALS.set(&Globals::new(), || module.fold_with(&mut strip()))
+}
+
+/// Compile JSX elements to calls to `React.createElement()` so
+/// that modules which use JSX can be bundled.
+pub(crate) fn transform_jsx(
+    module: Module,
+    source_map: Arc<SourceMap>,
+) -> Module {
+    GLOBALS.set(&Globals::new(), || {
+        module.fold_with(&mut jsx(
+            source_map,
+            None::<SingleThreadedComments>,
+            Default::default(),
+        ))
+    })
+}
+
+/// Determine if a module contains JSX.
+pub(crate) fn has_jsx(module: &Module) -> bool {
+    let mut finder = JsxFinder { found: false };
+    module.visit_children_with(&mut finder);
+    finder.found
+}
+
+struct JsxFinder {
+    found: bool,
+}
+
+impl Visit for JsxFinder {
+    fn visit_jsx_element(&mut self, _: &JSXElement, _: &dyn VisitNode) {
+        self.found = true;
+    }
+
+    fn visit_jsx_fragment(&mut self, _: &JSXFragment, _: &dyn VisitNode) {
+        self.found = true;
+    }
+}
+
 /// Parse a module from a file.
 pub fn load_file<P: AsRef<Path>>(
     file: P,
     source_map: Option<Arc<SourceMap>>,
+) -> Result<(FileName, Arc<SourceMap>, Module)> {
+    load_file_with_comments(file, source_map, None)
+}
+
+/// Parse a module from a file collecting comments.
+pub fn load_file_with_comments<P: AsRef<Path>>(
+    file: P,
+    source_map: Option<Arc<SourceMap>>,
+    comments: Option<&dyn Comments>,
 ) -> Result<(FileName, Arc<SourceMap>, Module)> {
     let (sm, handler) = get_handler(source_map);
     let fm = sm.load_file(file.as_ref())?;
     let file_name = fm.name.clone();
 
-    let mut parser = get_parser(&*fm);
+    let mut parser = get_parser(&*fm, comments);
     for e in parser.take_errors() {
         e.into_diagnostic(&handler).emit();
     }
 
-    Ok((
-        file_name,
-        sm,
-        parser
-            .parse_module()
-            .map_err(|e| e.into_diagnostic(&handler).emit())
-            .expect("Failed to parse module"),
-    ))
+    let module = parser
+        .parse_module()
+        .map_err(|e| parse_error(&handler, &file_name, &sm, e))?;
+    let module = strip_types(&file_name, module);
+    Ok((file_name, sm, module))
 }
 
 /// Parse a module from a string.
@@ -89,19 +195,16 @@ pub fn load_code<S: AsRef<str>>(
 
     let file_name = fm.name.clone();
 
-    let mut parser = get_parser(&*fm);
+    let mut parser = get_parser(&*fm, None);
     for e in parser.take_errors() {
         e.into_diagnostic(&handler).emit();
     }
 
-    Ok((
-        file_name,
-        sm,
-        parser
-            .parse_module()
-            .map_err(|e| e.into_diagnostic(&handler).emit())
-            .expect("Failed to parse module"),
-    ))
+    let module = parser
+        .parse_module()
+        .map_err(|e| parse_error(&handler, &file_name, &sm, e))?;
+    let module = strip_types(&file_name, module);
+    Ok((file_name, sm, module))
 }
 
 // NOTE: The signature for Compiler.print() changes a lot
@@ -113,7 +216,9 @@ pub fn print<T>(
     source_map: Arc<SourceMap>,
     source_file_name: Option<&str>,
     output_path: Option<PathBuf>,
+    inline_sources_content: bool,
     source_maps_config: SourceMapsConfig,
+    minify: bool,
 ) -> Result<TransformOutput>
 where
     T: Node + VisitWith<IdentCollector>,
@@ -135,12 +240,12 @@ where
         node,
         source_file_name,
         output_path,
-        false,
+        inline_sources_content,
         JscTarget::Es2020,
         source_maps_config,
         &[],
         None,
-        false,
+        minify,
         None,
     )
 }
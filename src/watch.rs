@@ -0,0 +1,131 @@
+//! Run a build again when files change.
+//!
+//! Files are watched individually rather than by directory so that the
+//! watched files follow the module graph as dependencies are added and
+//! removed between builds. Watched paths are canonicalized so that
+//! relative paths (such as policy files given on the command line)
+//! match the paths reported when they change.
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use notify::{
+    watcher, DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher,
+};
+
+/// Delay used to debounce file system events.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Run a build and run it again whenever a watched file changes.
+///
+/// The build function is given the files that changed since the
+/// previous build (empty for the first build) and returns the files
+/// to watch. When a build fails the error is logged and the files
+/// from the previous build remain watched.
+pub fn watch<F>(files: HashSet<PathBuf>, mut build: F) -> Result<()>
+where
+    F: FnMut(&HashSet<PathBuf>) -> Result<HashSet<PathBuf>>,
+{
+    let (tx, rx) = channel();
+    let mut watcher = watcher(tx, DEBOUNCE)?;
+    let mut watched: HashSet<PathBuf> = HashSet::new();
+    let mut files = canonical(files);
+    let mut changed = HashSet::new();
+
+    loop {
+        match build(&changed) {
+            Ok(result) => files = canonical(result),
+            Err(e) => log::error!("{:#}", e),
+        }
+
+        // Editors may replace a file which removes the watch
+        // so changed files are always watched again
+        for file in changed.iter() {
+            if watched.remove(file) {
+                let _ = watcher.unwatch(file);
+            }
+        }
+        update(&mut watcher, &mut watched, &files);
+        log::info!("Watching {} file(s) for changes", watched.len());
+
+        changed = canonical(wait(&rx)?);
+        for file in changed.iter() {
+            log::info!("Changed {}", file.display());
+        }
+    }
+}
+
+/// Canonical paths for files so that they match the paths reported
+/// by file system events; files that no longer exist are unchanged.
+fn canonical(files: HashSet<PathBuf>) -> HashSet<PathBuf> {
+    files
+        .into_iter()
+        .map(|file| file.canonicalize().unwrap_or(file))
+        .collect()
+}
+
+/// Watch new files and stop watching files that are no longer required.
+fn update(
+    watcher: &mut RecommendedWatcher,
+    watched: &mut HashSet<PathBuf>,
+    files: &HashSet<PathBuf>,
+) {
+    let removed: Vec<PathBuf> = watched.difference(files).cloned().collect();
+    for file in removed {
+        let _ = watcher.unwatch(&file);
+        watched.remove(&file);
+    }
+
+    for file in files.difference(&watched.clone()) {
+        match watcher.watch(file, RecursiveMode::NonRecursive) {
+            Ok(_) => {
+                watched.insert(file.clone());
+            }
+            Err(e) => {
+                log::warn!("Unable to watch {} ({})", file.display(), e)
+            }
+        }
+    }
+}
+
+/// Wait for file system events and collect the changed files.
+///
+/// Events that arrive whilst collecting changes are included so that
+/// a single build is run for a batch of changes.
+fn wait(rx: &Receiver<DebouncedEvent>) -> Result<HashSet<PathBuf>> {
+    let mut changed = HashSet::new();
+    while changed.is_empty() {
+        match rx.recv() {
+            Ok(event) => collect(event, &mut changed),
+            Err(_) => bail!("file watcher was disconnected"),
+        }
+    }
+    while let Ok(event) = rx.try_recv() {
+        collect(event, &mut changed);
+    }
+    Ok(changed)
+}
+
+fn collect(event: DebouncedEvent, changed: &mut HashSet<PathBuf>) {
+    match event {
+        DebouncedEvent::Create(path)
+        | DebouncedEvent::Write(path)
+        | DebouncedEvent::Remove(path) => {
+            changed.insert(path);
+        }
+        DebouncedEvent::Rename(from, to) => {
+            changed.insert(from);
+            changed.insert(to);
+        }
+        DebouncedEvent::Error(e, path) => {
+            if let Some(path) = path {
+                log::warn!("Watch error for {} ({})", path.display(), e);
+            } else {
+                log::warn!("Watch error ({})", e);
+            }
+        }
+        _ => {}
+    }
+}
@@ -1,5 +1,6 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use std::path::Path;
+use std::process::Command;
 
 pub fn read_to_string<P: AsRef<Path>>(path: P) -> Result<String> {
     let mut content = std::fs::read_to_string(path)?;
@@ -8,3 +9,16 @@ pub fn read_to_string<P: AsRef<Path>>(path: P) -> Result<String> {
     }
     Ok(content)
 }
+
+/// Run a script with `node` and return the standard output.
+pub fn run_node<P: AsRef<Path>>(file: P) -> Result<String> {
+    let output = Command::new("node").arg(file.as_ref()).output()?;
+    if !output.status.success() {
+        bail!(
+            "node failed for {}: {}",
+            file.as_ref().display(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
@@ -0,0 +1,375 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+use basalt::{
+    bundler::RUNTIME_VERSION, policy::Policy, BundleConfig, SourceMapOptions,
+};
+
+use serde_json::{json, Value};
+
+use testing::{read_to_string, run_node};
+
+fn bundle(entry: &str, name: &str, runtime: Option<&str>) -> Result<String> {
+    let output = PathBuf::from("target/test-bundle").join(name);
+    basalt::bundle(BundleConfig {
+        modules: vec![PathBuf::from(entry)],
+        policy: vec![PathBuf::from("tests/mock-policy/policy.json")],
+        output: Some(output.clone()),
+        runtime: runtime.map(PathBuf::from),
+        ..Default::default()
+    })?;
+    Ok(read_to_string(output)?)
+}
+
+/// Bundle with the minimal runtime and evaluate the bundle with node,
+/// the entry point prints the imported values as JSON.
+fn evaluate(entry: &str, name: &str) -> Result<Value> {
+    let output = PathBuf::from("target/test-bundle").join(name);
+    basalt::bundle(BundleConfig {
+        modules: vec![PathBuf::from(entry)],
+        output: Some(output.clone()),
+        runtime: Some(PathBuf::from("tests/fixtures/runtime/runtime.js")),
+        ..Default::default()
+    })?;
+    Ok(serde_json::from_str(&run_node(output)?)?)
+}
+
+#[test]
+fn bundle_interop_react() -> Result<()> {
+    // Imports React (CommonJS) and calls require()
+    let code = bundle("tests/fixtures/react/main.js", "react.js", None)?;
+    assert!(code.contains("\"type\": \"mixed\""));
+    assert!(code.contains("__esModule"));
+    Ok(())
+}
+
+#[test]
+fn bundle_interop_values() -> Result<()> {
+    let result = evaluate("tests/fixtures/interop/main.js", "interop.js")?;
+    assert_eq!(
+        json!({
+            // module.exports is the default export of a CommonJS module
+            "cjs": { "named": "named", "answer": 42 },
+            "named": "named",
+            "namespace": {
+                "default": { "named": "named", "answer": 42 },
+                "answer": 42,
+            },
+            // Flagged exports are the namespace
+            "flagged": "flagged default",
+            "value": "flagged value",
+            // An ES module required by a CommonJS module
+            "result": {
+                "esModule": true,
+                "default": "esm default",
+                "name": "esm name",
+            },
+        }),
+        result
+    );
+    Ok(())
+}
+
+#[test]
+fn bundle_interop_mixed() -> Result<()> {
+    let result = evaluate("tests/fixtures/interop-mixed/main.js", "mixed.js")?;
+    assert_eq!(
+        json!({
+            "mixed": { "fromMixed": "named from mixed" },
+            "fromMixed": "named from mixed",
+            "esmName": "esm name",
+            "cjsName": "cjs name",
+        }),
+        result
+    );
+    Ok(())
+}
+
+#[test]
+fn bundle_interop_babel() -> Result<()> {
+    let code =
+        bundle("tests/fixtures/babel-core/main.js", "babel-core.js", None)?;
+    assert!(code.contains("\"type\": \"js\""));
+    assert!(!code.contains("\"type\": \"mixed\""));
+    Ok(())
+}
+
+#[test]
+fn bundle_vendored_runtime() -> Result<()> {
+    let code = bundle("tests/fixtures/react/main.js", "vendored.js", None)?;
+    let header = code.lines().next().unwrap();
+    assert_eq!(
+        format!(
+            "// basalt {} (LavaPack runtime {})",
+            env!("CARGO_PKG_VERSION"),
+            RUNTIME_VERSION
+        ),
+        header
+    );
+    Ok(())
+}
+
+#[test]
+fn bundle_custom_runtime() -> Result<()> {
+    let code = bundle(
+        "tests/fixtures/react/main.js",
+        "custom-runtime.js",
+        Some("tests/fixtures/runtime/runtime.js"),
+    )?;
+    let header = code.lines().next().unwrap();
+    assert!(header.starts_with("// basalt "));
+    assert!(header.ends_with("(LavaPack runtime 2.0.0)"));
+    assert!(code.contains("cache[id]"));
+    Ok(())
+}
+
+#[test]
+fn bundle_generate_policy() -> Result<()> {
+    let dir = PathBuf::from("tests/fixtures/bundle-policy");
+    let output = PathBuf::from("target/test-bundle/bundle-policy.js");
+    let policy_output = PathBuf::from("target/test-bundle/bundle-policy.json");
+    basalt::bundle(BundleConfig {
+        modules: vec![dir.join("main.js")],
+        policy: vec![dir.join("override.json")],
+        policy_output: Some(policy_output.clone()),
+        output: Some(output.clone()),
+        ..Default::default()
+    })?;
+
+    let expected: Policy =
+        serde_json::from_str(&read_to_string(dir.join("output.json"))?)?;
+    let policy: Policy = serde_json::from_str(&read_to_string(policy_output)?)?;
+    assert_eq!(expected, policy);
+
+    let code = read_to_string(output)?;
+    assert!(code.contains("\"process.env.DELAY\": true"));
+    assert!(code.contains("\"console.log\": true"));
+    Ok(())
+}
+
+fn bundle_policy(policy: &str, name: &str, strict: bool) -> Result<Policy> {
+    let dir = PathBuf::from("tests/fixtures/bundle-policy");
+    let output = PathBuf::from("target/test-bundle").join(name);
+    let policy_output = output.with_extension("json");
+    basalt::bundle(BundleConfig {
+        modules: vec![dir.join("main.js")],
+        policy: vec![dir.join(policy)],
+        policy_output: Some(policy_output.clone()),
+        strict_policy: strict,
+        output: Some(output),
+        ..Default::default()
+    })?;
+    Ok(serde_json::from_str(&read_to_string(policy_output)?)?)
+}
+
+#[test]
+fn bundle_trim_policy() -> Result<()> {
+    let policy = bundle_policy("unused.json", "trim-policy.js", false)?;
+    assert!(policy.resources.contains_key("bundle-pkg"));
+    assert!(!policy.resources.contains_key("missing-pkg"));
+    Ok(())
+}
+
+#[test]
+fn bundle_strict_policy() -> Result<()> {
+    let result = bundle_policy("unused.json", "strict-policy.js", true);
+    let err = result.unwrap_err().to_string();
+    assert!(err.contains("allows left-pad which is not imported"));
+
+    bundle_policy("override.json", "strict-override.js", true)?;
+    Ok(())
+}
+
+#[test]
+fn bundle_reproducible() -> Result<()> {
+    let entry = "tests/fixtures/bundle-policy/main.js";
+    let first = bundle(entry, "reproducible-1.js", None)?;
+    let second = bundle(entry, "reproducible-2.js", None)?;
+    assert_eq!(first, second);
+    assert!(first.contains("\"main.js\""));
+    assert!(first.contains("\"bundle-pkg@1.0.0/index.js\""));
+    Ok(())
+}
+
+#[test]
+fn bundle_source_map() -> Result<()> {
+    let output = PathBuf::from("target/test-bundle/source-map/bundle.js");
+    let source_map = PathBuf::from("target/test-bundle/maps/bundle.js.map");
+    basalt::bundle(BundleConfig {
+        modules: vec![PathBuf::from("tests/fixtures/bundle-policy/main.js")],
+        output: Some(output.clone()),
+        source_map: SourceMapOptions {
+            path: Some(source_map.clone()),
+            sources_content: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    })?;
+
+    let code = read_to_string(output)?;
+    assert!(code.ends_with("//# sourceMappingURL=../maps/bundle.js.map\n"));
+
+    let map: serde_json::Value =
+        serde_json::from_str(&read_to_string(source_map)?)?;
+    let sources: Vec<&str> = map["sources"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s.as_str().unwrap())
+        .collect();
+    assert!(sources.contains(&"../../../tests/fixtures/bundle-policy/main.js"));
+    assert!(map["sourcesContent"].as_array().is_some());
+    Ok(())
+}
+
+#[test]
+fn bundle_inline_source_map() -> Result<()> {
+    let output = PathBuf::from("target/test-bundle/inline-source-map.js");
+    basalt::bundle(BundleConfig {
+        modules: vec![PathBuf::from("tests/fixtures/bundle-policy/main.js")],
+        output: Some(output.clone()),
+        source_map: SourceMapOptions {
+            path: Some(PathBuf::from("inline")),
+            root: Some(PathBuf::from("tests/fixtures/bundle-policy")),
+            ..Default::default()
+        },
+        ..Default::default()
+    })?;
+
+    let code = read_to_string(output)?;
+    let prefix =
+        "//# sourceMappingURL=data:application/json;charset=utf-8;base64,";
+    let encoded = code
+        .lines()
+        .last()
+        .and_then(|line| line.strip_prefix(prefix))
+        .unwrap();
+    let map: serde_json::Value =
+        serde_json::from_slice(&base64::decode(encoded)?)?;
+    assert!(map["sources"]
+        .as_array()
+        .unwrap()
+        .contains(&serde_json::Value::from("main.js")));
+    Ok(())
+}
+
+#[test]
+fn bundle_minify() -> Result<()> {
+    let output = PathBuf::from("target/test-bundle/minify.js");
+    let source_map = PathBuf::from("target/test-bundle/minify.js.map");
+    basalt::bundle(BundleConfig {
+        modules: vec![PathBuf::from("tests/fixtures/react/main.js")],
+        output: Some(output.clone()),
+        source_map: SourceMapOptions {
+            path: Some(source_map.clone()),
+            ..Default::default()
+        },
+        minify: true,
+        ..Default::default()
+    })?;
+
+    let code = read_to_string(output)?;
+    assert!(code.contains(
+        "LavaPack.loadBundle(__modules__,__entryPoints__,__policy__)"
+    ));
+    assert!(code.contains("$h\u{200d}_imports"));
+
+    let map: serde_json::Value =
+        serde_json::from_str(&read_to_string(source_map)?)?;
+    assert!(map["sources"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|s| s.as_str().unwrap().ends_with("react/main.js")));
+    Ok(())
+}
+
+#[test]
+fn bundle_workspace_entries() -> Result<()> {
+    let dir = PathBuf::from("tests/fixtures/bundle-workspace/packages");
+    let output = PathBuf::from("target/test-bundle/workspace-entries.js");
+    let policy_output = output.with_extension("json");
+    basalt::bundle(BundleConfig {
+        modules: vec![dir.join("app/main.js"), dir.join("worker/worker.js")],
+        policy_output: Some(policy_output.clone()),
+        strict_policy: true,
+        output: Some(output.clone()),
+        ..Default::default()
+    })?;
+
+    // The package for the first entry point is the root package
+    // and the second entry point belongs to its own package
+    let policy: Policy = serde_json::from_str(&read_to_string(policy_output)?)?;
+    assert!(!policy.resources.contains_key("app-pkg"));
+    let worker = &policy.resources["worker-pkg"];
+    assert!(worker.globals.keys().any(|k| k == "setInterval"));
+    let packages: Vec<&String> = worker.packages.keys().collect();
+    assert_eq!(vec!["dep-pkg"], packages);
+
+    let code = read_to_string(output)?;
+    assert!(code.contains("\"worker-pkg@1.0.0/worker.js\""));
+    assert!(code.contains("\"package\": \"worker-pkg\""));
+    Ok(())
+}
+
+/// Write a project with a dependency outside the root package.
+fn write_sibling_project(dir: &Path) -> Result<PathBuf> {
+    let app = dir.join("app");
+    let shared = dir.join("shared");
+    std::fs::create_dir_all(&app)?;
+    std::fs::create_dir_all(&shared)?;
+    std::fs::write(
+        app.join("package.json"),
+        r#"{"name": "app-pkg", "version": "1.0.0"}"#,
+    )?;
+    std::fs::write(app.join("main.js"), "require('../shared/index.js');\n")?;
+    std::fs::write(
+        shared.join("package.json"),
+        r#"{"name": "shared-pkg", "version": "1.0.0"}"#,
+    )?;
+    std::fs::write(shared.join("index.js"), "module.exports = 42;\n")?;
+    Ok(app.join("main.js"))
+}
+
+#[test]
+fn bundle_outside_root() -> Result<()> {
+    let dir = PathBuf::from("target/test-bundle/outside-root");
+    let first = write_sibling_project(&dir.join("layout-a"))?;
+    let second = write_sibling_project(&dir.join("layout-b/nested"))?;
+
+    let mut bundles = Vec::new();
+    for (entry, name) in [(first, "a.js"), (second, "b.js")].iter() {
+        let output = dir.join(name);
+        basalt::bundle(BundleConfig {
+            modules: vec![entry.clone()],
+            output: Some(output.clone()),
+            ..Default::default()
+        })?;
+        bundles.push(read_to_string(output)?);
+    }
+
+    // Identifiers do not depend upon where the dependency is installed
+    assert_eq!(bundles[0], bundles[1]);
+    assert!(bundles[0].contains("\"shared-pkg@1.0.0/index.js\""));
+    assert!(bundles[0].contains("\"main.js\""));
+    Ok(())
+}
+
+#[test]
+fn bundle_multiple_entries() -> Result<()> {
+    let dir = PathBuf::from("tests/fixtures/bundle-entries");
+    let output = PathBuf::from("target/test-bundle/multiple-entries.js");
+    basalt::bundle(BundleConfig {
+        modules: vec![dir.join("main.js"), dir.join("worker.js")],
+        output: Some(output.clone()),
+        ..Default::default()
+    })?;
+
+    let code = read_to_string(output)?;
+    assert!(code.contains("\"main.js\""));
+    assert!(code.contains("\"worker.js\""));
+    // Shared modules are only included once
+    assert_eq!(code.matches("shared-module:").count(), 1);
+    Ok(())
+}
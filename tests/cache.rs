@@ -0,0 +1,167 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use basalt::cache::Cache;
+use basalt::module::graph::ModuleGraph;
+use basalt::policy::builder::PolicyBuilder;
+use basalt::BundleConfig;
+
+use testing::read_to_string;
+
+fn cache_dir(name: &str) -> Result<PathBuf> {
+    let dir = PathBuf::from("target/test-cache").join(name);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir)?;
+    }
+    Ok(dir)
+}
+
+#[test]
+fn cache_graph() -> Result<()> {
+    let dir = cache_dir("graph")?;
+    let entry = "tests/fixtures/basic-tree/main.js";
+
+    let first = ModuleGraph::new().cache(Some(Cache::new(&dir)?));
+    first.load(entry)?;
+    let module = first.get(entry).unwrap();
+    assert!(module.node().unwrap().module.is_some());
+
+    let second = ModuleGraph::new().cache(Some(Cache::new(&dir)?));
+    second.load(entry)?;
+    assert_eq!(first.modules().len(), second.modules().len());
+    let module = second.get(entry).unwrap();
+    let node = module.node().unwrap();
+    assert!(node.module.is_none());
+    assert_eq!(2, node.resolved.len());
+
+    let reparsed = second.reparse(module.file_name(), node)?.unwrap();
+    assert!(reparsed.module.is_some());
+    Ok(())
+}
+
+#[test]
+fn cache_policy() -> Result<()> {
+    let dir = cache_dir("policy")?;
+    let input = PathBuf::from("tests/policy/suppress/inline");
+    let expected = read_to_string(input.join("output.json"))?;
+
+    for _ in 0..2 {
+        let graph = ModuleGraph::new().cache(Some(Cache::new(&dir)?));
+        let builder = PolicyBuilder::with_graph(input.join("input.js"), graph)
+            .load()?
+            .analyze()?;
+        let debug = builder.debug().resources.get("suppress-inline").unwrap();
+        assert_eq!(5, debug.suppressions.len());
+
+        let policy = builder.finalize();
+        let result = serde_json::to_string_pretty(&policy)?;
+        assert_eq!(expected.trim_end(), result);
+    }
+    Ok(())
+}
+
+#[test]
+fn cache_analysis() -> Result<()> {
+    let dir = cache_dir("analysis")?;
+    let project = PathBuf::from("target/test-cache/analysis-project");
+    let package = project.join("node_modules/dup-pkg");
+    std::fs::create_dir_all(&package)?;
+    std::fs::write(
+        project.join("main.js"),
+        "require('dup-pkg/a');\nrequire('dup-pkg/b');\n",
+    )?;
+    std::fs::write(package.join("package.json"), r#"{"name": "dup-pkg"}"#)?;
+
+    // Identical contents at different paths
+    for name in &["a.js", "b.js"] {
+        std::fs::write(package.join(name), "module.exports = process;\n")?;
+    }
+
+    let graph = ModuleGraph::new().cache(Some(Cache::new(&dir)?));
+    let builder = PolicyBuilder::with_graph(project.join("main.js"), graph)
+        .load()?
+        .analyze()?;
+    assert!(builder.finalize().resources.contains_key("dup-pkg"));
+    assert_eq!(2, std::fs::read_dir(dir.join("analysis"))?.count());
+    Ok(())
+}
+
+#[test]
+fn cache_bundle() -> Result<()> {
+    let dir = cache_dir("bundle")?;
+    let output = PathBuf::from("target/test-cache/bundle.js");
+
+    let mut bundles = Vec::new();
+    for _ in 0..2 {
+        basalt::bundle(BundleConfig {
+            modules: vec![PathBuf::from(
+                "tests/fixtures/bundle-policy/main.js",
+            )],
+            output: Some(output.clone()),
+            cache_dir: Some(dir.clone()),
+            ..Default::default()
+        })?;
+        bundles.push(read_to_string(&output)?);
+    }
+
+    // Module functions are served from the cache on the second build
+    assert_eq!(2, std::fs::read_dir(dir.join("functions"))?.count());
+    assert_eq!(bundles[0], bundles[1]);
+    Ok(())
+}
+
+#[test]
+fn cache_transform() -> Result<()> {
+    let dir = cache_dir("transform")?;
+    let project = PathBuf::from("target/test-cache/transform-project");
+    std::fs::create_dir_all(&project)?;
+
+    // Identical contents at different paths
+    let mut sources = Vec::new();
+    for name in &["a.js", "b.js", "a.js"] {
+        let file = project.join(name);
+        std::fs::write(&file, "export const value = 42;\n")?;
+        let map = project.join(format!("{}.map", name));
+        basalt::transform(file, true, Some(dir.clone()), Some(map.clone()))?;
+        let map: serde_json::Value =
+            serde_json::from_str(&read_to_string(map)?)?;
+        sources.push(map["sources"].clone());
+    }
+
+    assert!(sources[0][0].as_str().unwrap().ends_with("a.js"));
+    assert!(sources[1][0].as_str().unwrap().ends_with("b.js"));
+    assert_eq!(sources[0], sources[2]);
+    assert_eq!(2, std::fs::read_dir(dir.join("transform"))?.count());
+    Ok(())
+}
+
+#[test]
+fn cache_package_exports() -> Result<()> {
+    let dir = cache_dir("package-exports")?;
+    let project = PathBuf::from("target/test-cache/package-exports-project");
+    let package = project.join("node_modules/exports-pkg");
+    std::fs::create_dir_all(&package)?;
+    std::fs::write(project.join("main.js"), "require('exports-pkg');\n")?;
+    std::fs::write(package.join("a.js"), "module.exports = 'a';\n")?;
+    std::fs::write(package.join("b.js"), "module.exports = 'b';\n")?;
+
+    let entry = project.join("main.js");
+    let mut resolved = Vec::new();
+    for target in &["./a.js", "./b.js"] {
+        std::fs::write(
+            package.join("package.json"),
+            format!(r#"{{"name": "exports-pkg", "exports": "{}"}}"#, target),
+        )?;
+        let graph = ModuleGraph::new().cache(Some(Cache::new(&dir)?));
+        graph.load(&entry)?;
+        let module = graph.get(&entry).unwrap();
+        let (_, file_name) = &module.node().unwrap().resolved[0];
+        resolved.push(file_name.to_string());
+    }
+
+    // Changing the package exports invalidates the cached resolution
+    assert!(resolved[0].ends_with("a.js"));
+    assert!(resolved[1].ends_with("b.js"));
+    Ok(())
+}
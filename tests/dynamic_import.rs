@@ -0,0 +1,85 @@
+use anyhow::Result;
+
+use swc_ecma_visit::VisitWith;
+
+use basalt::policy::analysis::dynamic_import::{
+    DynamicImport, DynamicImportAnalysis,
+};
+use basalt::swc_utils::load_code;
+
+fn analyze(code: &str) -> Result<Vec<DynamicImport>> {
+    let (_, source_map, module) = load_code(code, None, None)?;
+    let mut analysis = DynamicImportAnalysis::new(&source_map);
+    module.visit_children_with(&mut analysis);
+    Ok(analysis.imports)
+}
+
+#[test]
+fn dynamic_import_literal() -> Result<()> {
+    let code = r#"
+        const fs = require('fs');
+        const path = require(`path`);
+        import('./foo.js');"#;
+    let imports = analyze(code)?;
+    assert!(imports.is_empty());
+    Ok(())
+}
+
+#[test]
+fn dynamic_import_unresolvable() -> Result<()> {
+    let code = r#"
+        const name = 'fs';
+        const fs = require(name);
+        import(getName());"#;
+    let imports = analyze(code)?;
+    assert_eq!(2, imports.len());
+
+    let first = imports.get(0).unwrap();
+    assert_eq!("require", first.fn_name);
+    assert_eq!(None, first.pattern);
+    assert_eq!(3, first.line);
+
+    let second = imports.get(1).unwrap();
+    assert_eq!("import", second.fn_name);
+    assert_eq!(None, second.pattern);
+    assert_eq!(4, second.line);
+    Ok(())
+}
+
+#[test]
+fn dynamic_import_concat() -> Result<()> {
+    let code = r#"
+        const locale = require('./locale/' + lang);
+        const messages = require('./locale/' + lang + '/messages.json');"#;
+    let imports = analyze(code)?;
+    assert_eq!(2, imports.len());
+    assert_eq!(
+        Some("./locale/*"),
+        imports.get(0).unwrap().pattern.as_deref()
+    );
+    assert_eq!(
+        Some("./locale/*/messages.json"),
+        imports.get(1).unwrap().pattern.as_deref()
+    );
+    Ok(())
+}
+
+#[test]
+fn dynamic_import_template() -> Result<()> {
+    let code = r#"
+        const locale = require(`./locale/${lang}.js`);
+        import(`./${a}${b}/index.js`);
+        import(`${name}`);"#;
+    let imports = analyze(code)?;
+    assert_eq!(3, imports.len());
+    assert_eq!(
+        Some("./locale/*.js"),
+        imports.get(0).unwrap().pattern.as_deref()
+    );
+    assert_eq!(
+        Some("./*/index.js"),
+        imports.get(1).unwrap().pattern.as_deref()
+    );
+    assert_eq!(None, imports.get(2).unwrap().pattern);
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use anyhow::Result;
+
+use basalt::error::ModuleError;
+use basalt::module::{graph::ModuleGraph, parser::parse};
+use basalt::swc_utils::load_code;
+
+#[test]
+fn error_parse() -> Result<()> {
+    let code = r#"
+        const a = 1;
+        const b = ;"#;
+    let err = load_code(code, None, None).unwrap_err();
+    match err.downcast_ref::<ModuleError>() {
+        Some(ModuleError::Parse { line, .. }) => assert_eq!(3, *line),
+        _ => panic!("expected a parse error"),
+    }
+    Ok(())
+}
+
+#[test]
+fn error_keep_going() -> Result<()> {
+    let graph = ModuleGraph::new().keep_going(true);
+    let (parsed, _) = parse(&graph, "tests/fixtures/keep-going/index.js")?;
+    assert_eq!(2, parsed);
+
+    let errors = graph.take_errors();
+    assert_eq!(2, errors.len());
+
+    match errors.get(0) {
+        Some(ModuleError::Resolve {
+            specifier, line, ..
+        }) => {
+            assert_eq!("./missing.js", specifier);
+            assert_eq!(3, *line);
+        }
+        _ => panic!("expected a resolve error"),
+    }
+
+    match errors.get(1) {
+        Some(ModuleError::Parse { file, line, .. }) => {
+            assert!(file.ends_with("broken.js"));
+            assert_eq!(3, *line);
+        }
+        _ => panic!("expected a parse error"),
+    }
+    Ok(())
+}
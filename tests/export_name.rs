@@ -124,3 +124,20 @@ fn export_name_13() -> Result<()> {
     assert_eq!(expected, result.code);
     Ok(())
 }
+
+#[test]
+fn export_name_source_map() -> Result<()> {
+    let (_, result) = transform("tests/transform/export-name-1/input.js")?;
+    let map: serde_json::Value = serde_json::from_str(&result.map.unwrap())?;
+    assert_eq!(
+        Some("tests/transform/export-name-1/input.js"),
+        map["sources"][0].as_str()
+    );
+
+    // Generated export calls map to the export declaration
+    let mappings = map["mappings"].as_str().unwrap();
+    let lines: Vec<&str> = mappings.split(';').collect();
+    assert!(!lines[2].is_empty());
+    assert!(!lines[3].is_empty());
+    Ok(())
+}
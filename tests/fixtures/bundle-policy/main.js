@@ -0,0 +1,2 @@
+const delay = require('bundle-pkg');
+delay(() => {});
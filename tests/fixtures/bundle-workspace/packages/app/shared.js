@@ -0,0 +1,3 @@
+module.exports = function shared(name) {
+  return name;
+};
@@ -0,0 +1,3 @@
+const shared = require('../app/shared.js');
+const tick = require('dep-pkg');
+setInterval(() => tick(shared('worker')), 1000);
@@ -0,0 +1,2 @@
+require('good-pkg');
+require('broken-pkg');
@@ -0,0 +1,5 @@
+require('exports-pkg');
+require('exports-pkg/feature');
+require('exports-pkg/utils/format');
+require('#internal/util');
+require('#dep');
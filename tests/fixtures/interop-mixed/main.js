@@ -0,0 +1,4 @@
+import mixed, { fromMixed } from './mixed.js';
+import { esmName, cjsName } from './mixed-export.js';
+
+console.log(JSON.stringify({ mixed, fromMixed, esmName, cjsName }));
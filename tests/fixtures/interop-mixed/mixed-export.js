@@ -0,0 +1,3 @@
+// ES module and CommonJS exports on the same exports object
+export const esmName = 'esm name';
+exports.cjsName = 'cjs name';
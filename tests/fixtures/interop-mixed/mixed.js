@@ -0,0 +1,3 @@
+// Only CommonJS exports so it is imported like a CommonJS module
+import { named } from 'cjs-pkg';
+exports.fromMixed = `${named} from mixed`;
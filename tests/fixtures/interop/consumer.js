@@ -0,0 +1,7 @@
+const esm = require('./esm.js');
+
+exports.result = {
+  esModule: esm.__esModule === true,
+  default: esm.default,
+  name: esm.name,
+};
@@ -0,0 +1,2 @@
+export default 'esm default';
+export const name = 'esm name';
@@ -0,0 +1,15 @@
+import cjs, { named } from 'cjs-pkg';
+import * as namespace from 'cjs-pkg';
+import flagged, { value } from 'flagged-pkg';
+import { result } from './consumer.js';
+
+console.log(
+  JSON.stringify({
+    cjs,
+    named,
+    namespace: { default: namespace.default, answer: namespace.answer },
+    flagged,
+    value,
+    result,
+  }),
+);
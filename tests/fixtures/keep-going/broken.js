@@ -0,0 +1,4 @@
+module.exports = {
+  foo: 'bar'
+  baz: 'qux',
+};
@@ -0,0 +1,3 @@
+const ok = require('./ok.js');
+const broken = require('./broken.js');
+const missing = require('./missing.js');
@@ -0,0 +1 @@
+Object.defineProperty(exports, '__esModule', { value: true });
@@ -0,0 +1 @@
+exports.foo = 1;
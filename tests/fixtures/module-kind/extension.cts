@@ -0,0 +1 @@
+export default function answer(): number { return 42; }
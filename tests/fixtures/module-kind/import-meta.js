@@ -0,0 +1 @@
+export const meta = import.meta.url;
@@ -0,0 +1,2 @@
+import foo from './esm.js';
+module.exports = foo;
@@ -0,0 +1,2 @@
+import { readFileSync } from 'fs';
+export const read = (file: string): string => readFileSync(file, 'utf8');
@@ -0,0 +1,9 @@
+let native;
+try {
+  native = require('missing-native');
+} catch (e) {}
+
+let present;
+try {
+  present = require('present-pkg');
+} catch (e) {}
@@ -0,0 +1,6 @@
+let native;
+try {
+  native = require('missing-native');
+} catch (e) {}
+
+const required = require('missing-native');
@@ -0,0 +1,3 @@
+const alias = require('my-alias');
+const peer = require('peer-pkg');
+module.exports = [alias(), peer()];
@@ -0,0 +1,2 @@
+const pkg = require('pnp-pkg');
+module.exports = pkg.version();
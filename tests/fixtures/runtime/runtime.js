@@ -0,0 +1,20 @@
+// Minimal runtime that runs the modules without any compartments.
+var LavaPack = {
+  loadBundle: function (modules, entryPoints, policy) {
+    var cache = {};
+    function load(id) {
+      if (cache[id]) {
+        return cache[id].exports;
+      }
+      var entry = modules.find(function (entry) {
+        return entry[0] === id;
+      });
+      var module = (cache[id] = { exports: {} });
+      entry[2](function (specifier) {
+        return load(entry[1][specifier]);
+      }, module, module.exports);
+      return module.exports;
+    }
+    entryPoints.forEach(load);
+  },
+};
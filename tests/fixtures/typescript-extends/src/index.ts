@@ -0,0 +1,3 @@
+import { name } from '@app/name';
+
+export const greeting: string = `hello ${name}`;
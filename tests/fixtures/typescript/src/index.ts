@@ -0,0 +1,11 @@
+import type { Options } from './types';
+import { location } from './util.js';
+import { format } from '@lib/format';
+import { View } from './view';
+import pkg from 'ts-pkg';
+
+const options: Options = { verbose: true };
+
+export default function main(): string {
+  return format(location(), options) + View + pkg;
+}
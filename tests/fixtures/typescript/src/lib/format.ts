@@ -0,0 +1,7 @@
+interface Options {
+  verbose: boolean;
+}
+
+export function format<T>(value: T, options: Options): string {
+  return options.verbose ? String(value) : '';
+}
@@ -0,0 +1,3 @@
+export function location(): string {
+  return window.location.href as string;
+}
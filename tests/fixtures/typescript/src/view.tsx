@@ -0,0 +1 @@
+export const View = <div title={document.title}>view</div>;
@@ -0,0 +1,74 @@
+use anyhow::Result;
+
+use basalt::module::{
+    graph::ModuleGraph, node::VisitedDependency, parser::parse,
+};
+
+#[test]
+fn graph_isolated() -> Result<()> {
+    let entry = "tests/fixtures/basic-tree/main.js";
+
+    let first = ModuleGraph::new();
+    let (first_parsed, _) = parse(&first, entry)?;
+
+    let second = ModuleGraph::new();
+    let (second_parsed, _) = parse(&second, entry)?;
+
+    assert_eq!(first_parsed, second_parsed);
+    assert_eq!(Some(0), first.id(entry));
+    assert_eq!(Some(0), second.id(entry));
+    Ok(())
+}
+
+#[test]
+fn graph_walk_once() -> Result<()> {
+    let graph = ModuleGraph::new();
+    let module = graph.parse_file("tests/fixtures/basic-tree/main.js")?;
+    let node = module.node().unwrap();
+
+    let mut walked = Vec::new();
+    node.walk(&graph, &mut |dep: VisitedDependency| {
+        walked.push(dep.spec);
+        Ok(())
+    })?;
+    assert_eq!(vec!["./foo.js", "./bar.js", "./qux.js"], walked);
+
+    let mut visited = 0;
+    node.visit(&graph, &mut |_: VisitedDependency| {
+        visited += 1;
+        Ok(())
+    })?;
+    assert_eq!(8, visited);
+    Ok(())
+}
+
+#[test]
+fn graph_load() -> Result<()> {
+    let graph = ModuleGraph::new();
+    graph.load("tests/fixtures/basic-tree/main.js")?;
+    assert_eq!(4, graph.modules().len());
+    Ok(())
+}
+
+#[test]
+fn graph_invalidate() -> Result<()> {
+    let graph = ModuleGraph::new();
+    graph.load("tests/fixtures/basic-tree/main.js")?;
+    let files = graph.files();
+    assert_eq!(4, files.len());
+
+    let foo = files
+        .iter()
+        .find(|p| p.ends_with("foo.js"))
+        .unwrap()
+        .clone();
+    let id = graph.id(&foo).unwrap();
+    let changed = vec![foo.clone()];
+    assert_eq!(1, graph.invalidate(&changed));
+    assert_eq!(3, graph.modules().len());
+
+    graph.load("tests/fixtures/basic-tree/main.js")?;
+    assert_eq!(4, graph.modules().len());
+    assert!(graph.id(&foo).unwrap() > id);
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use anyhow::Result;
+
+use swc_ecma_ast::*;
+
+use basalt::module::kind::{module_kind, ModuleKind};
+use basalt::static_module_record::transform::transform_mixed_module_function;
+use basalt::swc_utils::load_file;
+
+#[test]
+fn mixed_module_function() -> Result<()> {
+    let file = "tests/fixtures/module-kind/mixed.js";
+    let (_, _, module) = load_file(file, None)?;
+    assert_eq!(ModuleKind::Mixed, module_kind(None, &module)?);
+
+    let (meta, function) = transform_mixed_module_function(&module)?;
+    assert!(meta.imports.contains_key("./esm.js"));
+
+    // Functor arguments and the CommonJS bindings
+    let keys: Vec<String> = match &function.params[0].pat {
+        Pat::Object(pat) => pat
+            .props
+            .iter()
+            .filter_map(|prop| match prop {
+                ObjectPatProp::KeyValue(KeyValuePatProp {
+                    key: PropName::Ident(ident),
+                    ..
+                }) => Some(ident.sym.to_string()),
+                _ => None,
+            })
+            .collect(),
+        _ => panic!("expected an object pattern"),
+    };
+    assert_eq!(
+        vec!["imports", "liveVar", "onceVar", "require", "module", "exports"],
+        keys
+    );
+    Ok(())
+}
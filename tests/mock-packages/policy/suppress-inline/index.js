@@ -0,0 +1,16 @@
+const fs = require('fs');
+const path = require('path');
+
+function suppress() {
+  // basalt-ignore-next-line global:window
+  window.location.reload();
+  document.title = 'foo'; /* basalt-ignore global:document */
+  fs.readSync('test.txt'); // basalt-ignore builtin:fs
+  path.join('a', 'b');
+  // basalt-ignore-next-line
+  localStorage.clear();
+  navigator.userAgent;
+  const locale = 'en';
+  // basalt-ignore-next-line dynamic-import
+  require('./locale/' + locale);
+}
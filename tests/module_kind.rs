@@ -0,0 +1,59 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use basalt::module::kind::{module_kind, ModuleKind};
+use basalt::static_module_record;
+use basalt::swc_utils::load_file;
+
+fn kind(file: &str) -> Result<ModuleKind> {
+    let path = PathBuf::from("tests/fixtures/module-kind").join(file);
+    let (_, _, module) = load_file(&path, None)?;
+    module_kind(Some(&path), &module)
+}
+
+#[test]
+fn module_kind_extension() -> Result<()> {
+    assert_eq!(ModuleKind::Esm, kind("extension.mjs")?);
+    assert_eq!(ModuleKind::Cjs, kind("extension.cjs")?);
+    Ok(())
+}
+
+#[test]
+fn module_kind_package_type() -> Result<()> {
+    assert_eq!(ModuleKind::Esm, kind("type-module/index.js")?);
+    assert!(kind("type-commonjs/invalid.js").is_err());
+    Ok(())
+}
+
+#[test]
+fn module_kind_typescript_commonjs() -> Result<()> {
+    // Compiled to CommonJS by tsc so ES module syntax is interop
+    assert_eq!(ModuleKind::Mixed, kind("extension.cts")?);
+    assert_eq!(ModuleKind::Mixed, kind("type-commonjs/typed.ts")?);
+    Ok(())
+}
+
+#[test]
+fn module_kind_transform() -> Result<()> {
+    let transform = |file: &str| {
+        let path = PathBuf::from("tests/fixtures/module-kind").join(file);
+        static_module_record::transform(path.into(), Default::default())
+    };
+    // CommonJS modules are not static module records
+    assert!(transform("exports-property.js").is_err());
+    assert!(transform("type-commonjs/invalid.js").is_err());
+    assert!(transform("type-commonjs/typed.ts").is_ok());
+    assert!(transform("esm.js").is_ok());
+    Ok(())
+}
+
+#[test]
+fn module_kind_syntax() -> Result<()> {
+    assert_eq!(ModuleKind::Cjs, kind("exports-property.js")?);
+    assert_eq!(ModuleKind::Cjs, kind("define-property.js")?);
+    assert_eq!(ModuleKind::Esm, kind("esm.js")?);
+    assert_eq!(ModuleKind::Esm, kind("import-meta.js")?);
+    assert_eq!(ModuleKind::Mixed, kind("mixed.js")?);
+    Ok(())
+}
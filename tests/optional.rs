@@ -0,0 +1,118 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use indexmap::IndexSet;
+
+use swc_common::comments::SingleThreadedComments;
+use swc_ecma_dep_graph::analyze_dependencies;
+use swc_ecma_visit::VisitWith;
+
+use basalt::module::{graph::ModuleGraph, optional::OptionalAnalysis};
+use basalt::policy::builder::PolicyBuilder;
+use basalt::swc_utils::load_code;
+
+fn analyze(code: &str) -> Result<IndexSet<String>> {
+    let (_, _, module) = load_code(code, None, None)?;
+    let comments: SingleThreadedComments = Default::default();
+    let deps = analyze_dependencies(&module, &comments);
+    let mut analysis: OptionalAnalysis = Default::default();
+    module.visit_children_with(&mut analysis);
+    Ok(analysis.specifiers(&deps))
+}
+
+#[test]
+fn optional_try_block() -> Result<()> {
+    let code = r#"
+        const fs = require('fs');
+        let native;
+        try {
+            native = require('optional-native');
+        } catch (e) {
+            native = require('fallback');
+        }"#;
+    let specifiers = analyze(code)?;
+    assert_eq!(1, specifiers.len());
+    assert!(specifiers.contains("optional-native"));
+    Ok(())
+}
+
+#[test]
+fn optional_mixed_call_sites() -> Result<()> {
+    let code = r#"
+        try {
+            require('optional-native');
+            require('guarded-twice');
+        } catch {}
+        try { require('guarded-twice'); } catch {}
+        const native = require('optional-native');"#;
+    let specifiers = analyze(code)?;
+    assert_eq!(1, specifiers.len());
+    assert!(specifiers.contains("guarded-twice"));
+    Ok(())
+}
+
+#[test]
+fn optional_require_resolve() -> Result<()> {
+    let code = r#"
+        let hasNative = false;
+        try {
+            require.resolve('optional-native');
+            hasNative = true;
+        } catch {}
+        if (hasNative) require('optional-native');"#;
+    let specifiers = analyze(code)?;
+    assert_eq!(1, specifiers.len());
+    assert!(specifiers.contains("optional-native"));
+    Ok(())
+}
+
+#[test]
+fn optional_function_not_guarded() -> Result<()> {
+    let code = r#"
+        try {
+            module.exports = function load() {
+                return require('not-optional');
+            };
+            const lazy = () => require('also-not-optional');
+        } catch {}"#;
+    let specifiers = analyze(code)?;
+    assert!(specifiers.is_empty());
+    Ok(())
+}
+
+#[test]
+fn optional_require_resolve_not_guarded() -> Result<()> {
+    let code = r#"
+        const path = require.resolve('not-optional');
+        require(path);"#;
+    let specifiers = analyze(code)?;
+    assert!(specifiers.is_empty());
+    Ok(())
+}
+
+#[test]
+fn optional_graph_missing() -> Result<()> {
+    let entry = PathBuf::from("tests/fixtures/optional/main.js");
+    let graph = ModuleGraph::new();
+    let module = graph.load(&entry)?;
+    let node = module.node().unwrap();
+    assert!(node.is_missing("missing-native"));
+    assert!(!node.is_missing("present-pkg"));
+    assert!(graph.take_errors().is_empty());
+    assert_eq!(2, graph.modules().len());
+
+    let policy = PolicyBuilder::new(entry).load()?.analyze()?.finalize();
+    assert!(!policy.resources.contains_key("missing-native"));
+    let present = policy.resources.get("present-pkg").unwrap();
+    assert!(present.globals.keys().any(|k| k == "setTimeout"));
+    Ok(())
+}
+
+#[test]
+fn optional_graph_mixed() -> Result<()> {
+    let entry = PathBuf::from("tests/fixtures/optional/mixed.js");
+    let graph = ModuleGraph::new();
+    let err = graph.load(&entry).unwrap_err().to_string();
+    assert!(err.contains("missing-native"));
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use swc_common::FileName;
+use swc_ecma_loader::resolve::Resolve;
+
+use basalt::module::{
+    graph::ModuleGraph, pnp::PnpResolver, resolver::REQUIRE_CONDITIONS,
+};
+use basalt::policy::builder::PolicyBuilder;
+
+use testing::read_to_string;
+
+fn conditions() -> Vec<String> {
+    REQUIRE_CONDITIONS.iter().map(|c| c.to_string()).collect()
+}
+
+fn extract_dir(name: &str) -> Result<PathBuf> {
+    let dir = PathBuf::from("target/test-pnp").join(name);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir)?;
+    }
+    Ok(dir)
+}
+
+#[test]
+fn pnp_resolve_zip() -> Result<()> {
+    let dir = PathBuf::from("tests/fixtures/pnp");
+    let manifest = PnpResolver::find(dir.join("index.js"))?;
+    assert!(manifest.ends_with(".pnp.data.json"));
+
+    let resolver = PnpResolver::new(
+        &manifest,
+        conditions(),
+        Some(extract_dir("resolve-zip")?),
+    )?;
+    let base = FileName::Real(dir.join("index.js").canonicalize()?);
+    let file_name = resolver.resolve(&base, "pnp-pkg")?;
+
+    let expected = resolver
+        .extract_dir()
+        .join("pnp-pkg-npm-1.0.0-0123456789-abcdef0123")
+        .join("node_modules/pnp-pkg/lib/index.js");
+    assert_eq!(FileName::Real(expected), file_name);
+
+    assert!(resolver.resolve(&base, "not-a-dependency").is_err());
+    Ok(())
+}
+
+#[test]
+fn pnp_policy() -> Result<()> {
+    let dir = PathBuf::from("tests/fixtures/pnp");
+    let expected = read_to_string(dir.join("output.json"))?;
+    let entry = dir.join("index.js");
+    let graph =
+        ModuleGraph::with_pnp(PnpResolver::find(&entry)?, conditions(), None)?;
+    let builder = PolicyBuilder::with_graph(entry, graph).load()?.analyze()?;
+    let policy = builder.finalize();
+    let result = serde_json::to_string_pretty(&policy)?;
+    assert_eq!(expected.trim_end(), result);
+    Ok(())
+}
+
+#[test]
+fn pnp_extract_concurrent() -> Result<()> {
+    let dir = PathBuf::from("tests/fixtures/pnp");
+    let manifest = PnpResolver::find(dir.join("index.js"))?;
+    let extract = extract_dir("concurrent")?;
+    let base = FileName::Real(dir.join("index.js").canonicalize()?);
+
+    // Resolvers that share a directory extract the same archive
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let (manifest, extract, base) =
+                (manifest.clone(), extract.clone(), base.clone());
+            std::thread::spawn(move || -> Result<FileName> {
+                let resolver =
+                    PnpResolver::new(&manifest, conditions(), Some(extract))?;
+                Ok(resolver.resolve(&base, "pnp-pkg")?)
+            })
+        })
+        .collect();
+    for handle in handles {
+        let file_name = handle.join().unwrap()?;
+        assert!(file_name.to_string().ends_with("lib/index.js"));
+    }
+    Ok(())
+}
+
+#[test]
+fn pnp_locator_packages() -> Result<()> {
+    let dir = PathBuf::from("tests/fixtures/pnp-locator");
+    let entry = dir.join("index.js");
+    let manifest = PnpResolver::find(&entry)?;
+    let resolver = PnpResolver::new(
+        &manifest,
+        conditions(),
+        Some(extract_dir("locator")?),
+    )?;
+    let base = FileName::Real(entry.canonicalize()?);
+    let resolve = |base: &FileName, spec: &str| -> Result<PathBuf> {
+        match resolver.resolve(base, spec)? {
+            FileName::Real(path) => Ok(path),
+            file_name => panic!("unexpected file name {}", file_name),
+        }
+    };
+
+    // Aliased dependencies are identified by the package they refer to
+    let alias = resolve(&base, "my-alias")?;
+    let package = resolver.package(&alias).unwrap();
+    assert_eq!("alias-pkg", package.name);
+    assert_eq!(Some("1.0.0"), package.version.as_deref());
+
+    // Files for a virtual package are owned by the virtual locator
+    // which declares the resolved peer dependencies
+    let peer = resolve(&base, "peer-pkg")?;
+    let owner = resolver.owner(&peer).unwrap();
+    assert_eq!(Some("peer-pkg"), owner.0.as_deref());
+    assert_eq!(Some("virtual:0123456789#npm:1.0.0"), owner.1.as_deref());
+    let host = resolve(&FileName::Real(peer), "host-pkg")?;
+    assert!(host.ends_with("node_modules/host-pkg/index.js"));
+
+    // Files in the project do not belong to a package
+    assert!(resolver.package(&entry.canonicalize()?).is_none());
+
+    let graph = ModuleGraph::with_pnp(&manifest, conditions(), None)?;
+    let policy = PolicyBuilder::with_graph(entry, graph)
+        .load()?
+        .analyze()?
+        .finalize();
+    let packages: Vec<&String> = policy.resources.keys().collect();
+    assert_eq!(vec!["alias-pkg", "host-pkg", "peer-pkg"], packages);
+    assert!(policy.resources["alias-pkg"]
+        .globals
+        .keys()
+        .any(|k| k == "process.platform"));
+    assert!(policy.resources["peer-pkg"]
+        .packages
+        .keys()
+        .any(|k| k == "host-pkg"));
+    Ok(())
+}
@@ -0,0 +1,3 @@
+require('alias-pkg');
+require('alias-pkg/lib/deep.js');
+require('./node_modules/alias-pkg/lib/relative.js');
@@ -0,0 +1 @@
+require('suppress-inline');
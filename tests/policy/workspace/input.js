@@ -0,0 +1 @@
+require('ws-a');
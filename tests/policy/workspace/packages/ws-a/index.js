@@ -0,0 +1,3 @@
+require('./lib/util.js');
+require('ws-b');
+module.exports = process.cwd();
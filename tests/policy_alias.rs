@@ -0,0 +1,24 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use basalt::policy::builder::PolicyBuilder;
+
+use testing::read_to_string;
+
+#[test]
+fn policy_alias_package() -> Result<()> {
+    let dir = PathBuf::from("tests/policy/alias");
+    let expected = read_to_string(dir.join("output.json"))?;
+    let builder = PolicyBuilder::new(dir.join("input.js")).load()?.analyze()?;
+
+    // Aliased, deep and relative imports share the package.json identity
+    let debug = builder.debug().resources.get("real-pkg").unwrap();
+    let versions: Vec<&str> = debug.versions.iter().map(|v| &v[..]).collect();
+    assert_eq!(vec!["1.2.3"], versions);
+
+    let policy = builder.finalize();
+    let result = serde_json::to_string_pretty(&policy)?;
+    assert_eq!(expected.trim_end(), result);
+    Ok(())
+}
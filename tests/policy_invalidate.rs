@@ -0,0 +1,72 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use basalt::policy::builder::PolicyBuilder;
+
+#[test]
+fn policy_invalidate() -> Result<()> {
+    let project = PathBuf::from("target/test-policy/invalidate");
+    let package = project.join("node_modules/dep-pkg");
+    std::fs::create_dir_all(&package)?;
+    std::fs::write(project.join("main.js"), "require('dep-pkg');\n")?;
+    std::fs::write(
+        package.join("package.json"),
+        r#"{"name": "dep-pkg", "version": "1.0.0"}"#,
+    )?;
+    let index = package.join("index.js");
+    std::fs::write(&index, "setTimeout(() => {});\n")?;
+
+    let entry = project.join("main.js").canonicalize()?;
+    let builder = PolicyBuilder::new(entry.clone()).load()?.analyze()?;
+    let globals = |builder: &PolicyBuilder| -> Vec<String> {
+        builder.policy().resources["dep-pkg"]
+            .globals
+            .keys()
+            .cloned()
+            .collect()
+    };
+    assert_eq!(vec!["setTimeout"], globals(&builder));
+    let entry_id = builder.graph().id(&entry).unwrap();
+
+    std::fs::write(&index, "setInterval(() => {});\n")?;
+    let mut changed = HashSet::new();
+    changed.insert(index.canonicalize()?);
+    let builder = builder.invalidate(&changed).load()?.analyze()?;
+
+    // Only the changed module is loaded again
+    assert_eq!(vec!["setInterval"], globals(&builder));
+    assert_eq!(Some(entry_id), builder.graph().id(&entry));
+    Ok(())
+}
+
+#[test]
+fn policy_entry() -> Result<()> {
+    let project = PathBuf::from("target/test-policy/entry");
+    for (name, global) in
+        &[("dep-pkg", "setTimeout"), ("other-pkg", "setInterval")]
+    {
+        let package = project.join("node_modules").join(name);
+        std::fs::create_dir_all(&package)?;
+        std::fs::write(
+            package.join("package.json"),
+            format!(r#"{{"name": "{}", "version": "1.0.0"}}"#, name),
+        )?;
+        std::fs::write(package.join("index.js"), format!("{}();\n", global))?;
+    }
+    std::fs::write(project.join("main.js"), "require('dep-pkg');\n")?;
+    std::fs::write(project.join("other.js"), "require('other-pkg');\n")?;
+
+    let main = project.join("main.js").canonicalize()?;
+    let other = project.join("other.js").canonicalize()?;
+    let builder = PolicyBuilder::new(main).load()?.analyze()?;
+    assert!(builder.policy().resources.contains_key("dep-pkg"));
+
+    // The packages for the previous entry are not retained
+    let builder = builder.entry(other.clone()).load()?.analyze()?;
+    let expected = PolicyBuilder::new(other).load()?.analyze()?.finalize();
+    assert_eq!(&expected, builder.policy());
+    assert!(!builder.policy().resources.contains_key("dep-pkg"));
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use basalt::lint::{lint, DYNAMIC_IMPORT};
+use basalt::module::graph::ModuleGraph;
+use basalt::policy::analysis::suppression::Rule;
+use basalt::policy::builder::PolicyBuilder;
+
+use testing::read_to_string;
+
+#[test]
+fn policy_suppress_inline() -> Result<()> {
+    let dir = PathBuf::from("tests/policy/suppress/inline");
+    let expected = read_to_string(dir.join("output.json"))?;
+    let builder = PolicyBuilder::new(dir.join("input.js")).load()?.analyze()?;
+
+    let debug = builder.debug().resources.get("suppress-inline").unwrap();
+    let rules: Vec<(&Rule, &str)> = debug
+        .suppressions
+        .iter()
+        .map(|s| (&s.suppressed.rule, &s.suppressed.symbol[..]))
+        .collect();
+    assert_eq!(5, rules.len());
+    assert!(rules.contains(&(
+        &Rule::Global("window".to_string()),
+        "window.location.reload"
+    )));
+    assert!(rules
+        .contains(&(&Rule::Global("document".to_string()), "document.title")));
+    assert!(rules.contains(&(&Rule::Builtin("fs".to_string()), "fs.readSync")));
+    assert!(rules.contains(&(&Rule::All, "localStorage.clear")));
+    assert!(rules
+        .contains(&(&Rule::Lint(DYNAMIC_IMPORT.to_string()), DYNAMIC_IMPORT)));
+
+    let policy = builder.finalize();
+    let result = serde_json::to_string_pretty(&policy)?;
+    assert_eq!(expected.trim_end(), result);
+    Ok(())
+}
+
+#[test]
+fn lint_suppress_inline() -> Result<()> {
+    let dir = PathBuf::from("tests/policy/suppress/inline");
+    let report = lint(&ModuleGraph::new(), dir.join("input.js"))?;
+    assert!(report.warnings.is_empty());
+    assert_eq!(1, report.suppressions.len());
+
+    let suppressed = &report.suppressions[0].suppressed;
+    assert_eq!(Rule::Lint(DYNAMIC_IMPORT.to_string()), suppressed.rule);
+    assert_eq!(DYNAMIC_IMPORT, suppressed.symbol);
+    assert_eq!(vec![15], suppressed.lines);
+    Ok(())
+}
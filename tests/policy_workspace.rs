@@ -0,0 +1,27 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use basalt::policy::builder::PolicyBuilder;
+
+use testing::read_to_string;
+
+#[test]
+fn policy_workspace_packages() -> Result<()> {
+    let dir = PathBuf::from("tests/policy/workspace");
+    let expected = read_to_string(dir.join("output.json"))?;
+    let builder = PolicyBuilder::new(dir.join("input.js")).load()?.analyze()?;
+
+    // Symlinked packages are loaded from the workspace directory
+    let workspace = dir.join("packages").canonicalize()?;
+    for file in builder.graph().files() {
+        if !file.ends_with("input.js") {
+            assert!(file.starts_with(&workspace));
+        }
+    }
+
+    let policy = builder.finalize();
+    let result = serde_json::to_string_pretty(&policy)?;
+    assert_eq!(expected.trim_end(), result);
+    Ok(())
+}
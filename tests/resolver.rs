@@ -0,0 +1,110 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use swc_common::FileName;
+use swc_ecma_loader::resolve::Resolve;
+
+use basalt::module::resolver::{
+    PackageResolver, IMPORT_CONDITIONS, REQUIRE_CONDITIONS,
+};
+
+fn resolver(conditions: &[&str]) -> PackageResolver {
+    PackageResolver::new(conditions.iter().map(|c| c.to_string()).collect())
+}
+
+fn resolve(resolver: &PackageResolver, spec: &str) -> Result<PathBuf> {
+    resolve_in(resolver, "exports", spec)
+}
+
+fn resolve_in(
+    resolver: &PackageResolver,
+    fixture: &str,
+    spec: &str,
+) -> Result<PathBuf> {
+    let dir = std::env::current_dir()?
+        .join("tests/fixtures")
+        .join(fixture);
+    let base = FileName::Real(dir.join("index.js"));
+    match resolver.resolve(&base, spec)? {
+        FileName::Real(path) => Ok(path.strip_prefix(&dir)?.to_path_buf()),
+        _ => panic!("expected a real file name"),
+    }
+}
+
+#[test]
+fn resolver_exports_conditions() -> Result<()> {
+    let require = resolver(REQUIRE_CONDITIONS);
+    let import = resolver(IMPORT_CONDITIONS);
+    let browser = resolver(&["browser", "import"]);
+
+    assert_eq!(
+        PathBuf::from("node_modules/exports-pkg/cjs/index.js"),
+        resolve(&require, "exports-pkg")?
+    );
+    assert_eq!(
+        PathBuf::from("node_modules/exports-pkg/esm/index.mjs"),
+        resolve(&import, "exports-pkg")?
+    );
+    assert_eq!(
+        PathBuf::from("node_modules/exports-pkg/feature.js"),
+        resolve(&require, "exports-pkg/feature")?
+    );
+    assert_eq!(
+        PathBuf::from("node_modules/exports-pkg/feature-browser.js"),
+        resolve(&browser, "exports-pkg/feature")?
+    );
+    Ok(())
+}
+
+#[test]
+fn resolver_exports_patterns() -> Result<()> {
+    let require = resolver(REQUIRE_CONDITIONS);
+    assert_eq!(
+        PathBuf::from("node_modules/exports-pkg/lib/utils/format.js"),
+        resolve(&require, "exports-pkg/utils/format")?
+    );
+
+    // Not exported or excluded with null
+    assert!(resolve(&require, "exports-pkg/main.js").is_err());
+    assert!(resolve(&require, "exports-pkg/private/secret.js").is_err());
+    Ok(())
+}
+
+#[test]
+fn resolver_imports() -> Result<()> {
+    let require = resolver(REQUIRE_CONDITIONS);
+    assert_eq!(
+        PathBuf::from("src/internal/util.js"),
+        resolve(&require, "#internal/util")?
+    );
+    assert_eq!(
+        PathBuf::from("node_modules/exports-pkg/feature.js"),
+        resolve(&require, "#dep")?
+    );
+    assert_eq!(
+        PathBuf::from("src/dep.js"),
+        resolve(&resolver(&["browser"]), "#dep")?
+    );
+    assert!(resolve(&require, "#missing").is_err());
+    Ok(())
+}
+
+#[test]
+fn resolver_malformed_package() -> Result<()> {
+    let require = resolver(REQUIRE_CONDITIONS);
+    let fixture = "exports-malformed";
+
+    // The package.json of the project is not used for bare specifiers
+    assert_eq!(
+        PathBuf::from("node_modules/good-pkg/main.js"),
+        resolve_in(&require, fixture, "good-pkg")?
+    );
+
+    // Packages used for the resolution must be valid
+    let err = resolve_in(&require, fixture, "broken-pkg").unwrap_err();
+    assert!(format!("{:#}", err).contains("failed to parse"));
+    let err = resolve_in(&require, fixture, "#internal").unwrap_err();
+    assert!(format!("{:#}", err).contains("failed to parse"));
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use basalt::module::graph::ModuleGraph;
+use basalt::policy::builder::PolicyBuilder;
+use basalt::BundleConfig;
+
+use testing::read_to_string;
+
+#[test]
+fn typescript_graph() -> Result<()> {
+    let dir = PathBuf::from("tests/fixtures/typescript").canonicalize()?;
+    let graph = ModuleGraph::new();
+    graph.load(dir.join("src/index.ts"))?;
+
+    // Type only imports are removed so types.ts is not required
+    let mut files: Vec<PathBuf> = graph
+        .files()
+        .into_iter()
+        .map(|file| file.strip_prefix(&dir).unwrap().to_path_buf())
+        .collect();
+    files.sort();
+    let expected: Vec<PathBuf> = vec![
+        "node_modules/ts-pkg/index.ts",
+        "src/index.ts",
+        "src/lib/format.ts",
+        "src/util.ts",
+        "src/view.tsx",
+    ]
+    .into_iter()
+    .map(PathBuf::from)
+    .collect();
+    assert_eq!(expected, files);
+    Ok(())
+}
+
+#[test]
+fn typescript_policy() -> Result<()> {
+    let dir = PathBuf::from("tests/fixtures/typescript");
+    let expected = read_to_string(dir.join("output.json"))?;
+    let builder = PolicyBuilder::new(dir.join("src/index.ts"))
+        .load()?
+        .analyze()?;
+    let policy = builder.finalize();
+    let result = serde_json::to_string_pretty(&policy)?;
+    assert_eq!(expected.trim_end(), result);
+    Ok(())
+}
+
+#[test]
+fn typescript_bundle() -> Result<()> {
+    let output = PathBuf::from("target/test-bundle/typescript.js");
+    basalt::bundle(BundleConfig {
+        modules: vec![PathBuf::from("tests/fixtures/typescript/src/index.ts")],
+        output: Some(output.clone()),
+        ..Default::default()
+    })?;
+
+    let code = read_to_string(output)?;
+    assert!(code.contains("\"src/index.ts\""));
+    assert!(code.contains("\"src/view.tsx\""));
+    assert!(code.contains("\"ts-pkg@1.0.0/index.ts\""));
+
+    // Types are removed and JSX is compiled
+    assert!(!code.contains("interface Options"));
+    assert!(!code.contains("<div"));
+    assert!(code.contains("React.createElement(\"div\""));
+    Ok(())
+}
+
+#[test]
+fn typescript_extends() -> Result<()> {
+    let dir =
+        PathBuf::from("tests/fixtures/typescript-extends").canonicalize()?;
+    let graph = ModuleGraph::new();
+    graph.load(dir.join("src/index.ts"))?;
+
+    // Paths from the extended package config are relative to the
+    // base url declared in the project tsconfig.json
+    let mut files: Vec<PathBuf> = graph
+        .files()
+        .into_iter()
+        .map(|file| file.strip_prefix(&dir).unwrap().to_path_buf())
+        .collect();
+    files.sort();
+    let expected: Vec<PathBuf> = vec!["src/app/name.ts", "src/index.ts"]
+        .into_iter()
+        .map(PathBuf::from)
+        .collect();
+    assert_eq!(expected, files);
+    Ok(())
+}
@@ -0,0 +1,20 @@
+#!/usr/bin/env bash
+
+# Copy the LavaPack runtime installed by `yarn install` into the
+# source tree; the installed package must match RUNTIME_VERSION.
+
+set -e;
+
+PACKAGE="node_modules/@lavamoat/lavapack";
+TARGET="src/bundler/runtime.js";
+
+VERSION=$(sed -n 's/^pub const RUNTIME_VERSION: &str = "\(.*\)";$/\1/p' src/bundler/runtime.rs);
+INSTALLED=$(node -p "require('./$PACKAGE/package.json').version");
+
+if test "$VERSION" != "$INSTALLED"; then
+  echo "expected @lavamoat/lavapack $VERSION but $INSTALLED is installed" >&2;
+  exit 1;
+fi
+
+cp "$PACKAGE/src/runtime.js" "$TARGET";
+echo "vendored @lavamoat/lavapack $VERSION runtime in $TARGET";